@@ -0,0 +1,101 @@
+//! Lookup of the last time a station was heard on APRS, using the [aprs.fi] API.
+//!
+//! APRS-IS itself is a live stream without history, so the last-heard time of a
+//! beacon or object is taken from aprs.fi, which records everything heard on APRS-IS.
+//! An aprs.fi API key is required.
+//!
+//! [aprs.fi]: https://aprs.fi/page/api
+
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+
+use crate::transport::{encode_query, get_body, parse_json, Transport};
+use crate::{Error, Result};
+
+#[derive(Debug, Deserialize)]
+struct AprsResponse {
+    result: String,
+    description: Option<String>,
+    #[serde(default)]
+    entries: Vec<AprsEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AprsEntry {
+    name: String,
+    lasttime: String,
+}
+
+/// URL of the aprs.fi location query for `callsign`.
+pub(crate) fn request_url(callsign: &str, api_key: &str) -> String {
+    format!(
+        "https://api.aprs.fi/api/get?name={}&what=loc&apikey={}&format=json",
+        encode_query(callsign),
+        encode_query(api_key)
+    )
+}
+
+fn get_aprs_last_heard(transport: &dyn Transport, callsign: &str, api_key: &str) -> Result<i64> {
+    let body = get_body(transport, &request_url(callsign, api_key))?;
+    let response: AprsResponse = parse_json("aprs.fi API result", &body)?;
+    if response.result != "ok" {
+        return Err(Error::Api(format!(
+            "aprs.fi API error: {}",
+            response.description.unwrap_or(response.result)
+        )));
+    }
+    let entry = response
+        .entries
+        .iter()
+        .find(|e| e.name.eq_ignore_ascii_case(callsign))
+        .ok_or_else(|| Error::NotFound(format!("{} has never been heard on APRS", callsign)))?;
+    entry
+        .lasttime
+        .parse()
+        .map_err(|e| Error::parse("aprs.fi last heard time", e))
+}
+
+/// Return the number of seconds since the station or object was last heard on APRS.
+///
+/// Example:
+/// ```no_run
+/// use brandmeister::aprs::last_heard_seconds;
+/// let seconds :i64 = last_heard_seconds("ON0ABC-R", "my-aprs-fi-key").unwrap();
+/// ```
+#[cfg(feature = "ureq")]
+pub fn last_heard_seconds(callsign: &str, api_key: &str) -> Result<i64> {
+    last_heard_seconds_with(&crate::UreqTransport::default(), callsign, api_key)
+}
+
+/// Return the number of seconds since the station or object was last heard on APRS,
+/// performing the request with `transport`.
+pub fn last_heard_seconds_with(
+    transport: &dyn Transport,
+    callsign: &str,
+    api_key: &str,
+) -> Result<i64> {
+    Ok(last_heard_elapsed_with(transport, callsign, api_key)?.num_seconds())
+}
+
+/// Return the time elapsed since the station or object was last heard on APRS.
+///
+/// Example:
+/// ```no_run
+/// use brandmeister::aprs::last_heard_elapsed;
+/// let elapsed :chrono::Duration = last_heard_elapsed("ON0ABC-R", "my-aprs-fi-key").unwrap();
+/// ```
+#[cfg(feature = "ureq")]
+pub fn last_heard_elapsed(callsign: &str, api_key: &str) -> Result<Duration> {
+    last_heard_elapsed_with(&crate::UreqTransport::default(), callsign, api_key)
+}
+
+/// Return the time elapsed since the station or object was last heard on APRS, performing
+/// the request with `transport`.
+pub fn last_heard_elapsed_with(
+    transport: &dyn Transport,
+    callsign: &str,
+    api_key: &str,
+) -> Result<Duration> {
+    let last_heard = get_aprs_last_heard(transport, callsign, api_key)?;
+    Ok(Duration::seconds(Utc::now().timestamp() - last_heard))
+}
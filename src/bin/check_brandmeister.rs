@@ -16,6 +16,9 @@
 //!
 //! If you do not want to compile, you may find pre-built binaries on the [releases page](https://github.com/sgrimee/check_brandmeister/releases)
 //!
+//! On Windows, e.g. with NSClient++ or a Nagios agent, run `check_brandmeister.exe` from the release
+//! archive. Unix socket URLs for `--api-url` are not available there.
+//!
 //! ## Usage
 //!
 //! The check_brandmeister plugin is called by Nagios or LibreNMS but can be tested on the command-line.
@@ -30,16 +33,138 @@
 //! ```text
 //! USAGE:
 //!     check_brandmeister [OPTIONS] --repeater <repeater>
+//!     check_brandmeister [OPTIONS] --stdin
+//!     check_brandmeister check [OPTIONS] --repeater <repeater>
+//!     check_brandmeister check-api [OPTIONS]
+//!     check_brandmeister check-master-fleet [OPTIONS] --master <id>
+//!     check_brandmeister selftest [OPTIONS]
+//!     check_brandmeister snapshot [OPTIONS] --file <path>
+//!     check_brandmeister diff [OPTIONS] --baseline <path>
+//!     check_brandmeister export [OPTIONS] --status-file <path>
 //!
 //! OPTIONS:
-//!     -c, --critical <seconds>
-//!             Inactive time in seconds before Critical state [default: 900]
+//!         --api-url <url>
+//!             BrandMeister API to query instead of the public one, e.g. a local proxy at http://proxy:8080/v2 or unix:///var/run/bm-proxy.sock
+//!
+//!         --aprs-apikey <key>
+//!             aprs.fi API key, required with --aprs-call. Use env:NAME or file:PATH to read it from an environment variable or a file
+//!
+//!         --aprs-call <callsign>
+//!             APRS callsign of the repeater beacon or object to cross-check, e.g. ON0ABC-R
+//!
+//!         --cache-dir <dir>
+//!             Directory where API responses are shared between checks of the same poll cycle, and unknown repeater ids are remembered for 10 minutes
+//!
+//!         --cache-window <duration>
+//!             Length of the poll cycle window used with --cache-dir, in seconds or e.g. 5m [default: 60]
+//!
+//!         --check-duplicates
+//!             Warn when other devices seen recently are registered with the repeater's callsign
+//!
+//!         --contact <contact>
+//!             How BrandMeister admins can reach the operator, e.g. ON4ABC@example.org, sent in the User-Agent of every request
+//!
+//!         --derive-repeater-from-host <pattern>
+//!             Take the repeater id from the --host name when --repeater is not given, matching a PATTERN where {id} is the id and * any characters, e.g. bm-{id}.*
+//!
+//!         --dry-run
+//!             Print the resolved configuration, API requests and thresholds without checking
+//!
+//!         --debug-timing
+//!             Print the durations of the DNS resolution, first byte, body and parsing of each API request to standard error
+//!
+//!         --escalate-after <duration>
+//!             Report a repeater CRITICAL for longer than this as a long-term outage, e.g. 6h, requires --state-dir
+//!
+//!         --expect-field <name=value>
+//!             Warning when the field NAME of the repeater record is not VALUE, e.g. hardware=MMDVM, repeat to pin several fields
+//!
+//!         --expected-position <lat,lon>
+//!             Expected repeater position for --max-position-drift, instead of the first one recorded
+//!
+//!         --future-timestamp <policy>
+//!             How to report a last seen time in the future of the local clock: ok to keep the negative elapsed time, unknown, or clamp to now [default: clamp]
+//!
+//!         --label-separator <separator>
+//!             Separator between the repeater id and the metric in the perfdata labels of --stdin results, e.g. 270107_last_seen [default: _]
+//!
+//!         --max-clock-skew <duration>
+//!             Report UNKNOWN when the local clock is more than this off the API server clock, in seconds or e.g. 2m
+//!
+//!         --max-position-drift <distance>
+//!             Warning when the repeater position moved more than this distance, e.g. 500m or 2km
+//!
+//!         --max-reconnects <count>
+//!             Warning when the repeater reconnects more than COUNT times per hour, i.e. moves to another master or comes back after being seen stale, requires --state-dir
+//!
+//!         --max-requests-per-minute <n>
+//!             Pace the checks of --stdin to make at most N API requests per minute on average
+//!
+//!         --max-response-size <size>
+//!             Largest API response accepted, in bytes or e.g. 512k or 4M [default: 4M]
+//!
+//!         --metrics <list>
+//!             Comma separated metrics to report: last_seen, last_seen_min, status_code, linked_static_tg, linked_dynamic_tg, api_rtt_ms [default: last_seen]
+//!
+//!         --mmdvm-log <path>
+//!             MMDVMHost log file(s) to reconcile with the API, e.g. /var/log/pi-star/MMDVM-*.log
+//!
+//!         --network <network>
+//!             DMR network the repeater is checked on, only brandmeister so far [default: brandmeister]
+//!
+//!         --pretty
+//!             Colourised output aligned in columns, with relative times, for interactive use. Ignored when the standard output is not a terminal
+//!
+//!         --probe
+//!             Health probe mode: exit 0 when OK and 1 otherwise, with a single terse line
+//!
+//!         --pushgateway-url <url>
+//!             Prometheus Pushgateway to push the metrics to after each check, e.g. http://localhost:9091
+//!
+//!         --quiet
+//!             Print only the minutes since the repeater was last seen, or -1 if the check failed, for shell scripts, MRTG or collectd
+//!
+//!         --resolve <host:port:address>
+//!             Connect to address for host and port instead of resolving host, like curl, e.g. api.brandmeister.network:443:192.0.2.1
+//!
+//!         --retry-policy <policy>
+//!             Retry transient API failures, e.g. count=3,base=500ms,max=5s,jitter=full
+//!
+//!         --samples <n>
+//!             Number of consecutive threshold breaches before changing state, requires --state-dir [default: 1]
+//!
+//!         --state-dir <dir>
+//!             Directory where state and repeater callsign and city are kept between checks
+//!
+//!         --static-tg-count-crit <count>
+//!             Critical when the number of static talkgroups is at or below this value
+//!
+//!         --static-tg-count-warn <count>
+//!             Warning when the number of static talkgroups is at or below this value
+//!
+//!         --status-file <path>
+//!             JSON file gathering the latest result of each repeater checked, e.g. /var/www/html/bm-status.json
+//!
+//!         --stdin
+//!             Check the repeaters read from standard input, one per line as `ID [WARNING [CRITICAL]]`, printing one result line each
+//!
+//!         --strict-schema
+//!             Treat API responses not declared as JSON, and device lists where a single record is expected, as errors
+//!
+//!         --summary
+//!             After the checks of --stdin, print a summary line with the number of repeaters in each state, the runtime and the API calls made
+//!
+//!     -c, --critical <duration>
+//!             Inactive time before Critical state, in seconds or e.g. 15m, 2h30m [default: 900]
 //!
 //!     -h, --help
 //!             Print help information
 //!
 //!     -H, --host <hostname>
-//!             Ignored. For compatibility with nagios Host
+//!             Nagios host, ignored unless --derive-repeater-from-host is given
+//!
+//!     -o, --output <format>
+//!             Output format, nagios, json, checkmk or prometheus [default: nagios]
 //!
 //!     -r, --repeater <id>
 //!             BM repeater id, e.g. 270107
@@ -47,8 +172,153 @@
 //!     -V, --version
 //!             Print version information
 //!
-//!     -w, --warning <seconds>
-//!             Inactive time in seconds before Warning state [default: 600]
+//!     -w, --warning <duration>
+//!             Inactive time before Warning state, in seconds or e.g. 90s, 10m [default: 600]
+//!
+//! SUBCOMMANDS:
+//!     check
+//!             Check a repeater with the OPTIONS above, the default when no subcommand is given
+//!
+//!     check-api
+//!             Check reachability and response time of the BrandMeister API itself
+//!
+//!     check-master-fleet
+//!             Check how many repeaters of a master server have gone stale recently
+//!
+//!     diff
+//!             Report the fields of the repeater records changed since a snapshot
+//!
+//!     export
+//!             Render the results gathered in a --status-file, e.g. as an HTML status page
+//!
+//!     selftest
+//!             Verify API reachability, clock, aprs.fi credentials and cache and state directories
+//!
+//!     snapshot
+//!             Save the current API records of repeaters as a baseline for diff
+//!
+//! check-api OPTIONS:
+//!     -c, --critical <duration>
+//!             Response time before Critical state, in milliseconds or e.g. 5s [default: 5000]
+//!
+//!         --url <url>
+//!             API URL to probe, repeat to check several mirrors [default: --api-url or the public API]
+//!
+//!     -w, --warning <duration>
+//!             Response time before Warning state, in milliseconds or e.g. 1s [default: 1000]
+//!
+//! check-master-fleet OPTIONS:
+//!     -c, --critical <percent>
+//!             Percentage of stale repeaters for Critical state [default: 25]
+//!
+//!         --ignore-after <duration>
+//!             Inactive time after which a repeater is ignored as decommissioned, in seconds or e.g. 7d [default: 7d]
+//!
+//!     -m, --master <id>
+//!             BM master server id, e.g. 2701
+//!
+//!         --stale-after <duration>
+//!             Inactive time after which a repeater counts as stale, in seconds or e.g. 15m [default: 900]
+//!
+//!     -w, --warning <percent>
+//!             Percentage of stale repeaters for Warning state [default: 10]
+//!
+//! diff OPTIONS:
+//!         --baseline <path>
+//!             Snapshot file to compare with
+//!
+//!         --snapshot <path>
+//!             Later snapshot file to compare, instead of the current API records
+//!
+//! export OPTIONS:
+//!         --format <format>
+//!             Format of the export: html, inventory (JSON), inventory-csv or grafana-dashboard [default: html]
+//!
+//!         --status-file <path>
+//!             Status file written by checks run with --status-file
+//!
+//! selftest OPTIONS:
+//!         --aprs-apikey <key>
+//!             aprs.fi API key to validate, or env:NAME or file:PATH to read it from
+//!
+//!         --aprs-call <callsign>
+//!             APRS callsign to look up when validating the aprs.fi API key
+//!
+//!         --cache-dir <dir>
+//!             Cache directory to verify
+//!
+//!         --state-dir <dir>
+//!             State directory to verify
+//!
+//! snapshot OPTIONS:
+//!         --file <path>
+//!             Snapshot file to write
+//!
+//!     -r, --repeater <id>
+//!             ID of a repeater to include, repeat for several
+//!
+//!         --status-file <path>
+//!             Include the repeaters recorded in this status file
+//! ```
+//!
+//! `check-api` checks the BrandMeister API itself, so that repeater services can depend on it and
+//! an API outage does not page once per repeater:
+//! ```text
+//! check_brandmeister check-api
+//!
+//! BrandMeister API is OK: 1 of 1 endpoints reachable| 'api_rtt'=182ms;1000;5000;0;
+//! ```
+//!
+//! `check-master-fleet` gives network coordinators a single canary service per master server:
+//! ```text
+//! check_brandmeister check-master-fleet --master 2701
+//!
+//! BrandMeister master 2701 is OK: 3 of 42 repeaters stale| 'stale_repeaters'=3;;;0; 'stale_percent'=7%;10;25;0;
+//! ```
+//!
+//! `--stdin` checks many repeaters in one run, e.g. from cron, reading one repeater id per line,
+//! optionally followed by its own warning and critical thresholds. It prints one line per repeater,
+//! with perfdata labels prefixed by the repeater id, and exits with the worst state. `--summary` adds a
+//! final line with the number of repeaters in each state, the runtime and the API calls made:
+//! ```text
+//! printf '270107\n270108 30m 1h\n' | check_brandmeister --stdin --summary
+//!
+//! BrandMeister repeater 270107 is OK: online status; last seen at 2026-10-14T04:28:58Z| '270107_last_seen'=101s;600;900;0;
+//! BrandMeister repeater 270108 is OK: online status; last seen at 2026-10-14T03:51:12Z| '270108_last_seen'=2367s;1800;3600;0;
+//! OK: 2 repeaters checked in 0.412s with 2 API calls: 2 OK, 0 WARNING, 0 CRITICAL, 0 UNKNOWN
+//! ```
+//!
+//! `--max-requests-per-minute` spaces the checks so that a large fleet stays within a request
+//! budget toward the public API, e.g. `--max-requests-per-minute 60` for one request a second.
+//!
+//! `--status-file` gathers the latest result of each repeater in a JSON file that web pages can fetch,
+//! and `export` renders it as a colour-coded HTML status page for clubs without a monitoring system:
+//! ```text
+//! check_brandmeister --stdin --status-file /var/lib/check_brandmeister/status.json < repeaters.txt
+//! check_brandmeister export --status-file /var/lib/check_brandmeister/status.json > /var/www/html/status.html
+//! ```
+//!
+//! The same status file lists the repeaters whose hardware, firmware, frequencies and location
+//! `export --format inventory` or `inventory-csv` fetches for an asset inventory:
+//! ```text
+//! check_brandmeister export --status-file /var/lib/check_brandmeister/status.json --format inventory-csv > repeaters.csv
+//! ```
+//!
+//! With `--pushgateway-url`, `export --format grafana-dashboard` generates a Grafana dashboard graphing
+//! the pushed metrics of these repeaters, ready to import:
+//! ```text
+//! check_brandmeister export --status-file /var/lib/check_brandmeister/status.json --format grafana-dashboard > brandmeister.json
+//! ```
+//!
+//! `snapshot` saves the full API records of repeaters as a baseline, and `diff` warns about any field
+//! that changed since, e.g. a frequency, a firmware or a location edited in SelfCare:
+//! ```text
+//! check_brandmeister snapshot --file /var/lib/check_brandmeister/baseline.json -r 270107 -r 270108
+//! check_brandmeister diff --baseline /var/lib/check_brandmeister/baseline.json
+//!
+//! BrandMeister repeater records is WARNING: 1 changed fields since 2026-10-14T04:51:37Z
+//!
+//! 270107 tx: "439.4375" -> "439.5000"| 'changed_fields'=1;1;;0;
 //! ```
 //!
 //! [BrandMeister]: https://brandmeister.network/
@@ -57,48 +327,1540 @@
 
 #![warn(missing_docs)]
 
-// use anyhow::Result;
-use clap::Parser;
-use nagiosplugin::{Metric, Resource, Runner, ServiceState, TriggerIfValue, Unit};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
 
-use brandmeister::last_seen_seconds;
+use chrono::{Duration, Utc};
+use clap::error::ErrorKind;
+use clap::{Args as ClapArgs, Parser, Subcommand, ValueEnum};
+use nagiosplugin::ServiceState;
+
+use brandmeister::check::{
+    check, check_api, check_drift, check_master_fleet, selftest, ApiCheckOptions, CheckOptions,
+    CheckOutcome, DriftCheckOptions, FleetCheckOptions, FutureTimestamp, Metric, NagiosState,
+    SelftestOptions,
+};
+use brandmeister::mmdvm::wildcard_match;
+use brandmeister::network::Network;
+use brandmeister::output::{self, sanitize_text, Nagios, OutputFormatter, Prometheus};
+use brandmeister::snapshot::Snapshot;
+#[cfg(unix)]
+use brandmeister::UnixSocketTransport;
+use brandmeister::{
+    duration, inventory, pushgateway, status_file, user_agent, BmClient, HttpResponse,
+    RepeaterInventory, ResolveOverride, RetryPolicy, Timings, Transport, UreqTransport,
+};
 
 /// Simple program to greet a person
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[derive(Parser, Debug, Clone)]
+#[command(author, version, about, long_about = None, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    check: CheckArgs,
+    /// BrandMeister API to query instead of the public one, e.g. a local proxy at
+    /// http://proxy:8080/v2 or unix:///var/run/bm-proxy.sock
+    #[arg(long, global = true, value_name = "URL")]
+    api_url: Option<String>,
+    /// How BrandMeister admins can reach the operator, e.g. ON4ABC@example.org, sent in the
+    /// User-Agent of every request
+    #[arg(long, global = true, value_name = "CONTACT")]
+    contact: Option<String>,
+    /// Connect to ADDRESS for HOST and PORT instead of resolving HOST, like curl, e.g.
+    /// api.brandmeister.network:443:192.0.2.1
+    #[arg(long, global = true, value_name = "HOST:PORT:ADDRESS")]
+    resolve: Vec<ResolveOverride>,
+    /// Health probe mode: exit 0 when OK and 1 otherwise, with a single terse line
+    #[arg(long, global = true, conflicts_with = "output")]
+    probe: bool,
+    /// Output format, nagios, json, checkmk or prometheus
+    #[arg(short, long, global = true, value_name = "format", default_value = "nagios", value_parser = parse_output)]
+    output: Arc<dyn OutputFormatter>,
+    /// Phases of the API requests, recorded with --debug-timing
+    #[arg(skip)]
+    timings: Arc<Timings>,
+}
+
+/// Options of the repeater check, given either without subcommand or after `check`.
+#[derive(ClapArgs, Debug, Clone)]
+struct CheckArgs {
     /// ID of the BrandMeister repeater to check
-    #[arg(short, long)]
-    repeater: u32,
-    /// Threshold in seconds for warning state
-    #[arg(short, long, default_value_t = 600)]
-    warning: i64,
-    /// Threshold in seconds for critical state
-    #[arg(short, long, default_value_t = 900)]
-    critical: i64,
-    /// Ignored, for compatibility with nagios host
+    #[arg(short, long, required_unless_present_any = ["stdin", "derive_repeater_from_host"])]
+    repeater: Option<u32>,
+    /// Check the repeaters read from standard input, one per line as `ID [WARNING [CRITICAL]]`,
+    /// printing one result line each
+    #[arg(long, conflicts_with_all = ["repeater", "probe", "dry_run"])]
+    stdin: bool,
+    /// After the checks of --stdin, print a summary line with the number of repeaters in each
+    /// state, the runtime and the API calls made
+    #[arg(long, requires = "stdin")]
+    summary: bool,
+    /// Separator between the repeater id and the metric in the perfdata labels of --stdin
+    /// results, e.g. 270107_last_seen
+    #[arg(
+        long,
+        value_name = "SEPARATOR",
+        default_value = "_",
+        requires = "stdin"
+    )]
+    label_separator: String,
+    /// Pace the checks of --stdin to make at most N API requests per minute on average
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    max_requests_per_minute: Option<u32>,
+    /// Threshold for warning state, in seconds or e.g. 90s, 10m, 2h30m
+    #[arg(short, long, value_name = "DURATION", default_value = "600", value_parser = parse_seconds)]
+    warning: Duration,
+    /// Threshold for critical state, in seconds or e.g. 90s, 15m, 2h30m
+    #[arg(short, long, value_name = "DURATION", default_value = "900", value_parser = parse_seconds)]
+    critical: Duration,
+    /// Nagios host, ignored unless --derive-repeater-from-host is given
     #[arg(short = 'H', long)]
     host: Option<String>,
+    /// Take the repeater id from the --host name when --repeater is not given, matching a
+    /// PATTERN where {id} is the id and * any characters, e.g. bm-{id}.*
+    #[arg(long, value_name = "PATTERN", value_parser = parse_host_pattern)]
+    derive_repeater_from_host: Option<String>,
+    /// APRS callsign of the repeater beacon or object to cross-check, e.g. ON0ABC-R
+    #[arg(long, requires = "aprs_apikey")]
+    aprs_call: Option<String>,
+    /// aprs.fi API key, required with --aprs-call. Use env:NAME or file:PATH to read it from
+    /// an environment variable or a file
+    #[arg(long, value_name = "KEY", value_parser = parse_secret)]
+    aprs_apikey: Option<String>,
+    /// MMDVMHost log file(s) to reconcile with the API, e.g. /var/log/pi-star/MMDVM-*.log
+    #[arg(long, value_name = "PATH")]
+    mmdvm_log: Option<String>,
+    /// DMR network the repeater is checked on, only brandmeister so far
+    #[arg(long, value_name = "NETWORK", default_value = "brandmeister")]
+    network: Network,
+    /// Directory where API responses are shared between checks of the same poll cycle, and
+    /// unknown repeater ids are remembered for 10 minutes
+    #[arg(long, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+    /// Length of the poll cycle window used with --cache-dir, in seconds or e.g. 5m
+    #[arg(long, value_name = "DURATION", default_value = "60", value_parser = parse_seconds)]
+    cache_window: Duration,
+    /// Warning when the number of static talkgroups is at or below this value
+    #[arg(long, value_name = "COUNT")]
+    static_tg_count_warn: Option<i64>,
+    /// Critical when the number of static talkgroups is at or below this value
+    #[arg(long, value_name = "COUNT")]
+    static_tg_count_crit: Option<i64>,
+    /// Warning when the repeater position moved more than this distance, e.g. 500m or 2km
+    #[arg(long, value_name = "DISTANCE", value_parser = parse_distance)]
+    max_position_drift: Option<i64>,
+    /// Expected repeater position for --max-position-drift, instead of the first one recorded
+    #[arg(long, value_name = "LAT,LON", value_parser = parse_position)]
+    expected_position: Option<(f64, f64)>,
+    /// Warning when the field NAME of the repeater record is not VALUE, e.g. hardware=MMDVM,
+    /// repeat to pin several fields
+    #[arg(long, value_name = "NAME=VALUE", value_parser = parse_field)]
+    expect_field: Vec<(String, String)>,
+    /// Warn when other devices seen recently are registered with the repeater's callsign
+    #[arg(long)]
+    check_duplicates: bool,
+    /// Directory where state and repeater callsign and city are kept between checks
+    #[arg(long, value_name = "DIR")]
+    state_dir: Option<PathBuf>,
+    /// Number of consecutive threshold breaches before changing state, requires --state-dir
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    samples: u32,
+    /// Report a repeater CRITICAL for longer than this as a long-term outage, e.g. 6h, requires
+    /// --state-dir
+    #[arg(long, value_name = "DURATION", value_parser = parse_seconds)]
+    escalate_after: Option<Duration>,
+    /// Warning when the repeater reconnects more than COUNT times per hour, i.e. moves to
+    /// another master or comes back after being seen stale, requires --state-dir
+    #[arg(long, value_name = "COUNT")]
+    max_reconnects: Option<u32>,
+    /// Report UNKNOWN when the local clock is more than this off the API server clock, in
+    /// seconds or e.g. 2m
+    #[arg(long, value_name = "DURATION", value_parser = parse_seconds)]
+    max_clock_skew: Option<Duration>,
+    /// How to report a last seen time in the future of the local clock: ok to keep the
+    /// negative elapsed time, unknown, or clamp to now
+    #[arg(long, value_name = "POLICY", default_value = "clamp")]
+    future_timestamp: FutureTimestamp,
+    /// Comma separated metrics to report: last_seen, last_seen_min, status_code,
+    /// linked_static_tg, linked_dynamic_tg, api_rtt_ms
+    #[arg(
+        long,
+        value_name = "LIST",
+        value_delimiter = ',',
+        default_value = "last_seen"
+    )]
+    metrics: Vec<Metric>,
+    /// Prometheus Pushgateway to push the metrics to after each check, e.g. http://localhost:9091
+    #[arg(long, value_name = "URL")]
+    pushgateway_url: Option<String>,
+    /// JSON file gathering the latest result of each repeater checked, e.g.
+    /// /var/www/html/bm-status.json
+    #[arg(long, value_name = "PATH")]
+    status_file: Option<PathBuf>,
+    /// Retry transient API failures, e.g. count=3,base=500ms,max=5s,jitter=full
+    #[arg(long, value_name = "POLICY")]
+    retry_policy: Option<RetryPolicy>,
+    /// Print the resolved configuration, API requests and thresholds without checking
+    #[arg(long, conflicts_with = "probe")]
+    dry_run: bool,
+    /// Print the durations of the DNS resolution, first byte, body and parsing of each API
+    /// request to standard error
+    #[arg(long)]
+    debug_timing: bool,
+    /// Largest API response accepted, in bytes or e.g. 512k or 4M
+    #[arg(long, value_name = "SIZE", default_value = "4M", value_parser = parse_size)]
+    max_response_size: usize,
+    /// Treat API responses not declared as JSON, and device lists where a single record is
+    /// expected, as errors
+    #[arg(long)]
+    strict_schema: bool,
+    /// Print only the minutes since the repeater was last seen, or -1 if the check failed, for
+    /// shell scripts, MRTG or collectd
+    #[arg(long, conflicts_with_all = ["probe", "output", "stdin", "dry_run"])]
+    quiet: bool,
+    /// Colourised output aligned in columns, with relative times, for interactive use. Ignored
+    /// when the standard output is not a terminal
+    #[arg(long, conflicts_with_all = ["probe", "output", "quiet", "dry_run"])]
+    pretty: bool,
 }
 
-fn do_check() -> anyhow::Result<Resource, anyhow::Error> {
-    let args = Args::parse();
+/// Checks other than the default repeater check.
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Check a repeater, the default when no subcommand is given
+    Check(Box<CheckArgs>),
+    /// Check reachability and response time of the BrandMeister API itself
+    CheckApi(ApiArgs),
+    /// Check how many repeaters of a master server have gone stale recently
+    CheckMasterFleet(FleetArgs),
+    /// Verify API reachability, clock, aprs.fi credentials and cache and state directories
+    Selftest(SelftestArgs),
+    /// Render the results gathered in a --status-file, e.g. as an HTML status page
+    Export(ExportArgs),
+    /// Save the current API records of repeaters as a baseline for diff
+    Snapshot(SnapshotArgs),
+    /// Report the fields of the repeater records changed since a snapshot
+    Diff(DiffArgs),
+}
 
-    let seconds = last_seen_seconds(args.repeater)?;
-    let resource = Resource::new(format!("BrandMeister repeater {}", args.repeater))
-        .with_description("online status")
-        .with_result(
-            Metric::new("last_seen", seconds)
-                .with_minimum(0)
-                .with_unit(Unit::Seconds)
-                .with_thresholds(args.warning, args.critical, TriggerIfValue::Greater),
+#[derive(ClapArgs, Debug, Clone)]
+struct ExportArgs {
+    /// Status file written by checks run with --status-file
+    #[arg(long, value_name = "PATH")]
+    status_file: PathBuf,
+    /// Format of the export
+    #[arg(long, value_enum, default_value_t = ExportFormat::Html)]
+    format: ExportFormat,
+}
+
+#[derive(ClapArgs, Debug, Clone)]
+struct SnapshotArgs {
+    /// Snapshot file to write
+    #[arg(long, value_name = "PATH")]
+    file: PathBuf,
+    /// ID of a repeater to include, repeat for several
+    #[arg(
+        short,
+        long = "repeater",
+        value_name = "ID",
+        required_unless_present = "status_file"
+    )]
+    repeaters: Vec<u32>,
+    /// Include the repeaters recorded in this status file
+    #[arg(long, value_name = "PATH")]
+    status_file: Option<PathBuf>,
+}
+
+#[derive(ClapArgs, Debug, Clone)]
+struct DiffArgs {
+    /// Snapshot file to compare with
+    #[arg(long, value_name = "PATH")]
+    baseline: PathBuf,
+    /// Later snapshot file to compare, instead of the current API records
+    #[arg(long, value_name = "PATH")]
+    snapshot: Option<PathBuf>,
+}
+
+impl DiffArgs {
+    fn options(
+        &self,
+        transport: Arc<dyn Transport>,
+        api_url: Option<&str>,
+    ) -> brandmeister::Result<DriftCheckOptions> {
+        let mut builder = DriftCheckOptions::builder()
+            .transport(transport)
+            .baseline(&self.baseline);
+        if let Some(url) = api_url {
+            builder = builder.api_url(url);
+        }
+        if let Some(path) = &self.snapshot {
+            builder = builder.snapshot(path);
+        }
+        builder.build()
+    }
+}
+
+/// Format of the `export` subcommand.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ExportFormat {
+    /// Standalone HTML page with a colour-coded status table
+    Html,
+    /// JSON inventory of the hardware, firmware, frequencies and location of the repeaters
+    Inventory,
+    /// CSV inventory of the hardware, firmware, frequencies and location of the repeaters
+    InventoryCsv,
+    /// Grafana dashboard JSON graphing the metrics pushed with --pushgateway-url
+    GrafanaDashboard,
+}
+
+#[derive(ClapArgs, Debug, Clone)]
+struct ApiArgs {
+    /// API URL to probe, repeat to check several mirrors [default: --api-url or the public API]
+    #[arg(long, value_name = "URL")]
+    url: Vec<String>,
+    /// Threshold for warning state, in milliseconds or e.g. 1s
+    #[arg(short, long, value_name = "DURATION", default_value = "1000", value_parser = parse_millis)]
+    warning: Duration,
+    /// Threshold for critical state, in milliseconds or e.g. 5s
+    #[arg(short, long, value_name = "DURATION", default_value = "5000", value_parser = parse_millis)]
+    critical: Duration,
+}
+
+impl ApiArgs {
+    /// Options probing the URLs of `--url`, or the API of `api` reached with its transport.
+    fn options(
+        &self,
+        transport: Arc<dyn Transport>,
+        api: (Arc<dyn Transport>, Option<String>),
+    ) -> brandmeister::Result<ApiCheckOptions> {
+        let mut builder = ApiCheckOptions::builder()
+            .warn(self.warning)
+            .crit(self.critical);
+        builder = match api {
+            (api_transport, Some(url)) if self.url.is_empty() => builder
+                .transport(api_transport)
+                .url(format!("{}/", url.trim_end_matches('/'))),
+            _ => builder.transport(transport),
+        };
+        self.url
+            .iter()
+            .fold(builder, |builder, url| builder.url(url))
+            .build()
+    }
+}
+
+#[derive(ClapArgs, Debug, Clone)]
+struct FleetArgs {
+    /// ID of the BrandMeister master server, e.g. 2701
+    #[arg(short, long, value_name = "ID")]
+    master: u32,
+    /// Inactive time after which a repeater counts as stale, in seconds or e.g. 15m
+    #[arg(long, value_name = "DURATION", default_value = "900", value_parser = parse_seconds)]
+    stale_after: Duration,
+    /// Inactive time after which a repeater is ignored as decommissioned, in seconds or e.g. 7d
+    #[arg(long, value_name = "DURATION", default_value = "7d", value_parser = parse_seconds)]
+    ignore_after: Duration,
+    /// Percentage of stale repeaters for warning state
+    #[arg(short, long, value_name = "PERCENT", default_value_t = 10)]
+    warning: i64,
+    /// Percentage of stale repeaters for critical state
+    #[arg(short, long, value_name = "PERCENT", default_value_t = 25)]
+    critical: i64,
+}
+
+impl FleetArgs {
+    fn options(
+        &self,
+        transport: Arc<dyn Transport>,
+        api_url: Option<&str>,
+    ) -> brandmeister::Result<FleetCheckOptions> {
+        let builder = match api_url {
+            Some(url) => FleetCheckOptions::builder().api_url(url),
+            None => FleetCheckOptions::builder(),
+        };
+        builder
+            .transport(transport)
+            .master(self.master)
+            .stale_after(self.stale_after)
+            .ignore_after(self.ignore_after)
+            .warn(self.warning)
+            .crit(self.critical)
+            .build()
+    }
+}
+
+#[derive(ClapArgs, Debug, Clone)]
+struct SelftestArgs {
+    /// aprs.fi API key to validate, or env:NAME or file:PATH to read it from
+    #[arg(long, value_name = "KEY", value_parser = parse_secret)]
+    aprs_apikey: Option<String>,
+    /// APRS callsign to look up when validating the aprs.fi API key
+    #[arg(long, requires = "aprs_apikey")]
+    aprs_call: Option<String>,
+    /// Cache directory to verify
+    #[arg(long, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+    /// State directory to verify
+    #[arg(long, value_name = "DIR")]
+    state_dir: Option<PathBuf>,
+}
+
+impl SelftestArgs {
+    fn options(
+        &self,
+        transport: Arc<dyn Transport>,
+        api: (Arc<dyn Transport>, Option<String>),
+    ) -> SelftestOptions {
+        let mut opts = SelftestOptions::new().transport(transport);
+        if let (api_transport, Some(url)) = api {
+            opts = opts.api(url, Some(api_transport));
+        }
+        if let Some(key) = &self.aprs_apikey {
+            opts = opts.aprs(self.aprs_call.clone(), key);
+        }
+        if let Some(dir) = &self.cache_dir {
+            opts = opts.cache_dir(dir);
+        }
+        if let Some(dir) = &self.state_dir {
+            opts = opts.state_dir(dir);
+        }
+        opts
+    }
+}
+
+impl Args {
+    /// Parse the command line, exiting with a single line UNKNOWN message if it is invalid.
+    ///
+    /// Clap exits with code 2 on usage errors, which nagios would report as CRITICAL.
+    fn parse_or_exit() -> Self {
+        match Args::try_parse() {
+            Ok(args) => args.without_check_command(),
+            Err(e) if matches!(e.kind(), ErrorKind::DisplayHelp | ErrorKind::DisplayVersion) => {
+                e.exit()
+            }
+            Err(e) => exit_invalid_arguments(&clap_error_summary(&e)),
+        }
+    }
+
+    /// Move the options of the explicit check subcommand to the top level, as they are the same
+    /// as giving them without subcommand.
+    fn without_check_command(mut self) -> Self {
+        match self.command.take() {
+            Some(Command::Check(check)) => self.check = *check,
+            command => self.command = command,
+        }
+        self
+    }
+
+    /// Run the selected check, exiting with UNKNOWN if its options are invalid.
+    fn run(&self) -> CheckOutcome {
+        if let Some(Command::Check(_)) = self.command {
+            return self.clone().without_check_command().run();
+        }
+        if self.check.dry_run && self.command.is_none() {
+            match self.options() {
+                Ok(opts) => println!("{}", opts.dry_run()),
+                Err(e) => exit_invalid_arguments(&e.to_string()),
+            }
+            std::process::exit(0);
+        }
+        let outcome = match &self.command {
+            Some(Command::Export(export)) => print_exported(self.export(export)),
+            Some(Command::Snapshot(snapshot)) => print_exported(self.snapshot(snapshot)),
+            Some(Command::CheckApi(args)) => self
+                .api()
+                .and_then(|api| args.options(self.transport(), api))
+                .map(|opts| check_api(&opts)),
+            Some(Command::CheckMasterFleet(fleet)) => self.api().and_then(|(transport, url)| {
+                fleet
+                    .options(transport, url.as_deref())
+                    .map(|opts| check_master_fleet(&opts))
+            }),
+            Some(Command::Selftest(test)) => self
+                .api()
+                .map(|api| selftest(&test.options(self.transport(), api))),
+            Some(Command::Diff(diff)) => self.api().and_then(|(transport, url)| {
+                diff.options(transport, url.as_deref())
+                    .map(|opts| check_drift(&opts))
+            }),
+            Some(Command::Check(_)) | None => self
+                .options()
+                .map(|opts| self.publish(opts.repeater(), check(&opts))),
+        };
+        outcome.unwrap_or_else(|e| exit_invalid_arguments(&e.to_string()))
+    }
+
+    /// Render the status file of `export`, or the inventory of the repeaters it covers.
+    fn export(&self, export: &ExportArgs) -> brandmeister::Result<String> {
+        Ok(match export.format {
+            ExportFormat::Html => status_file::html(&export.status_file)?,
+            ExportFormat::Inventory => {
+                let items = self.inventory(&export.status_file)?;
+                serde_json::to_string_pretty(&items).expect("inventory serializes to JSON") + "\n"
+            }
+            ExportFormat::InventoryCsv => inventory::csv(&self.inventory(&export.status_file)?),
+            ExportFormat::GrafanaDashboard => status_file::grafana_dashboard(&export.status_file)?,
+        })
+    }
+
+    /// Fetch the inventory of the repeaters recorded in the status file at `path`.
+    fn inventory(&self, path: &Path) -> brandmeister::Result<Vec<RepeaterInventory>> {
+        inventory::collect(&self.client()?, &status_file::repeaters(path)?)
+    }
+
+    /// Save the snapshot of the repeaters of `args`, returning a summary line.
+    fn snapshot(&self, args: &SnapshotArgs) -> brandmeister::Result<String> {
+        let mut repeaters = args.repeaters.clone();
+        if let Some(path) = &args.status_file {
+            repeaters.extend(status_file::repeaters(path)?);
+        }
+        repeaters.sort_unstable();
+        repeaters.dedup();
+        Snapshot::take(&self.client()?, &repeaters)?.save(&args.file)?;
+        Ok(format!(
+            "Saved the records of {} repeaters to {}\n",
+            repeaters.len(),
+            args.file.display()
+        ))
+    }
+
+    /// Client of the BrandMeister API selected by --api-url.
+    fn client(&self) -> brandmeister::Result<BmClient> {
+        let (transport, api_url) = self.api()?;
+        let client = BmClient::with_transport(transport);
+        Ok(match api_url {
+            Some(url) => client.with_api_url(url),
+            None => client,
+        })
+    }
+
+    /// Check each repeater listed on standard input and print one line per repeater.
+    ///
+    /// Lines hold a repeater id optionally followed by warning and critical thresholds that
+    /// override the command line ones. Blank lines and lines starting with `#` are skipped.
+    /// Returns the worst state of all checks.
+    fn run_stdin(&self) -> NagiosState {
+        let start = Instant::now();
+        let mut worst = NagiosState::Ok;
+        // Number of checks in each state, indexed by exit code.
+        let mut counts = [0; 4];
+        // Aligning columns needs all the rows, which are printed at the end, and so does
+        // grouping the samples of each Prometheus metric.
+        let mut rows = Vec::new();
+        let mut exposition = Vec::new();
+        let prometheus = self.output.name() == Prometheus.name();
+        for (number, line) in std::io::stdin().lines().enumerate() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => exit_invalid_arguments(&format!("reading standard input: {}", e)),
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(per_minute) = self.check.max_requests_per_minute {
+                pace(start, per_minute);
+            }
+            let outcome = self.for_stdin_line(line).and_then(|args| {
+                let opts = args.options()?;
+                Ok((opts.repeater(), args.publish(opts.repeater(), check(&opts))))
+            });
+            match outcome {
+                Ok((repeater, mut outcome)) => {
+                    worst = worst.worst(outcome.state);
+                    counts[outcome.state.exit_code() as usize] += 1;
+                    if self.pretty() {
+                        rows.push(PrettyRow::new(&outcome));
+                    } else if prometheus {
+                        exposition.push((repeater.to_string(), outcome));
+                    } else {
+                        if self.nagios() {
+                            // Keep the series of each repeater apart in graphing tools.
+                            for p in &mut outcome.perfdata {
+                                p.name =
+                                    format!("{}{}{}", repeater, self.check.label_separator, p.name);
+                            }
+                        }
+                        println!("{}", self.render_line(&outcome));
+                    }
+                }
+                Err(e) => {
+                    worst = worst.worst(NagiosState::Unknown);
+                    counts[NagiosState::Unknown.exit_code() as usize] += 1;
+                    if self.pretty() {
+                        rows.push(PrettyRow {
+                            state: NagiosState::Unknown,
+                            name: format!("line {}", number + 1),
+                            seen: String::new(),
+                            details: e.to_string(),
+                        });
+                        continue;
+                    }
+                    let e =
+                        brandmeister::Error::InvalidOptions(format!("line {}: {}", number + 1, e));
+                    let outcome = CheckOutcome::failed(format!("line {}", number + 1), &e);
+                    if prometheus {
+                        let repeater = line.split_whitespace().next().unwrap_or_default();
+                        exposition.push((repeater.to_string(), outcome));
+                    } else {
+                        println!("{}", self.render_line(&outcome));
+                    }
+                }
+            }
+        }
+        if !rows.is_empty() {
+            println!("{}", pretty_output(&rows));
+        }
+        if !exposition.is_empty() {
+            println!("{}", Prometheus.format_repeaters(&exposition));
+        }
+        if self.check.summary {
+            let runtime = start.elapsed().as_millis() as u64;
+            let api_calls = API_CALLS.load(Ordering::Relaxed);
+            let summary = self.summary(worst, counts, runtime, api_calls);
+            if prometheus {
+                // A comment line keeps the exposition valid.
+                println!("# {}", summary);
+            } else {
+                println!("{}", summary);
+            }
+        }
+        worst
+    }
+
+    /// Render the summary line of a --stdin run, as a JSON object with --output json.
+    fn summary(
+        &self,
+        state: NagiosState,
+        counts: [u32; 4],
+        runtime: u64,
+        api_calls: usize,
+    ) -> String {
+        let [ok, warning, critical, unknown] = counts;
+        if self.output.name() == "json" {
+            return serde_json::json!({
+                "summary": {
+                    "state": state,
+                    "ok": ok,
+                    "warning": warning,
+                    "critical": critical,
+                    "unknown": unknown,
+                    "runtime_ms": runtime,
+                    "api_calls": api_calls,
+                }
+            })
+            .to_string();
+        }
+        format!(
+            "{}: {} repeaters checked in {}s with {} API calls: {} OK, {} WARNING, {} CRITICAL, {} UNKNOWN",
+            state,
+            ok + warning + critical + unknown,
+            runtime as f64 / 1000.0,
+            api_calls,
+            ok,
+            warning,
+            critical,
+            unknown
+        )
+    }
+
+    /// These arguments with the repeater and thresholds of a line of standard input.
+    fn for_stdin_line(&self, line: &str) -> brandmeister::Result<Args> {
+        let invalid = |message: String| brandmeister::Error::InvalidOptions(message);
+        let mut fields = line.split_whitespace();
+        let mut args = self.clone();
+        let repeater = fields.next().unwrap_or_default();
+        args.check.repeater = Some(
+            repeater
+                .parse()
+                .map_err(|_| invalid(format!("invalid repeater id {}", repeater)))?,
         );
-    Ok(resource)
+        if let Some(warning) = fields.next() {
+            args.check.warning = parse_seconds(warning).map_err(invalid)?;
+        }
+        if let Some(critical) = fields.next() {
+            args.check.critical = parse_seconds(critical).map_err(invalid)?;
+        }
+        if let Some(extra) = fields.next() {
+            return Err(invalid(format!(
+                "unexpected {}, expected ID [WARNING [CRITICAL]]",
+                extra
+            )));
+        }
+        Ok(args)
+    }
+
+    /// Print the phases of the API requests to standard error with --debug-timing.
+    fn print_timings(&self) {
+        if self.check.debug_timing {
+            eprint!("{}", self.timings);
+        }
+    }
+
+    /// Whether --pretty is selected and the output is a terminal that shows it.
+    fn pretty(&self) -> bool {
+        self.check.pretty && std::io::stdout().is_terminal()
+    }
+
+    /// Whether the nagios output format is selected.
+    fn nagios(&self) -> bool {
+        self.output.name() == Nagios.name()
+    }
+
+    /// Render the outcome of a line of standard input, reduced to a single line in the
+    /// nagios format. The other formats already give one line per check.
+    fn render_line(&self, outcome: &CheckOutcome) -> String {
+        match self.nagios() {
+            true => single_line(&self.output.format(outcome)),
+            false => self.output.format(outcome),
+        }
+    }
+
+    /// Render `outcome` in the selected output format.
+    fn render(&self, outcome: &CheckOutcome) -> String {
+        if self.pretty() {
+            return pretty_output(&[PrettyRow::new(outcome)]);
+        }
+        self.output.format(outcome)
+    }
+
+    /// Push the outcome of `repeater` to the Pushgateway and record it in the status file if
+    /// configured, warning when either fails.
+    fn publish(&self, repeater: u32, mut outcome: CheckOutcome) -> CheckOutcome {
+        if let Some(url) = &self.check.pushgateway_url {
+            // Counted like the checks' requests, for the --summary of --stdin runs.
+            let transport = CountingTransport(self.transport());
+            if let Err(e) = pushgateway::push(&transport, url, repeater, &outcome) {
+                if outcome.error.is_none() {
+                    outcome.state = outcome.state.worst(NagiosState::Warning);
+                }
+                outcome
+                    .messages
+                    .push(format!("push to Pushgateway failed: {}", e));
+            }
+        }
+        if let Some(path) = &self.check.status_file {
+            if let Err(e) = status_file::record(path, repeater, &outcome) {
+                if outcome.error.is_none() {
+                    outcome.state = outcome.state.worst(NagiosState::Warning);
+                }
+                outcome
+                    .messages
+                    .push(format!("writing status file failed: {}", e));
+            }
+        }
+        outcome
+    }
+
+    /// The HTTP transport, with the --resolve overrides.
+    fn transport(&self) -> Arc<dyn Transport> {
+        let mut transport = UreqTransport::with_resolve(self.resolve.clone())
+            .with_user_agent(user_agent(self.contact.as_deref()));
+        if self.check.debug_timing {
+            transport = transport.with_timings(self.timings.clone());
+        }
+        Arc::new(transport)
+    }
+
+    /// The transport and URL of the BrandMeister API, which --api-url may point to a Unix
+    /// socket whose server gets the API paths from the root.
+    fn api(&self) -> brandmeister::Result<(Arc<dyn Transport>, Option<String>)> {
+        let Some(socket) = self
+            .api_url
+            .as_deref()
+            .and_then(|u| u.strip_prefix("unix://"))
+        else {
+            return Ok((self.transport(), self.api_url.clone()));
+        };
+        #[cfg(unix)]
+        return Ok((
+            Arc::new(
+                UnixSocketTransport::new(socket)
+                    .with_user_agent(user_agent(self.contact.as_deref())),
+            ),
+            Some("http://localhost".to_string()),
+        ));
+        #[cfg(not(unix))]
+        return Err(brandmeister::Error::InvalidOptions(format!(
+            "Unix socket {} is not supported on this platform",
+            socket
+        )));
+    }
+
+    /// The repeater given with -r, or derived from the host with --derive-repeater-from-host.
+    fn repeater(&self) -> brandmeister::Result<Option<u32>> {
+        let (Some(pattern), None) = (&self.check.derive_repeater_from_host, self.check.repeater)
+        else {
+            return Ok(self.check.repeater);
+        };
+        let host = self.check.host.as_deref().unwrap_or_default();
+        host_repeater(pattern, host).map(Some).ok_or_else(|| {
+            brandmeister::Error::InvalidOptions(format!(
+                "no repeater id in host {:?} matching {}",
+                host, pattern
+            ))
+        })
+    }
+
+    fn options(&self) -> brandmeister::Result<CheckOptions> {
+        let (transport, api_url) = self.api()?;
+        let mut builder = CheckOptions::builder()
+            .transport(Arc::new(CountingTransport(transport)))
+            .warn(self.check.warning)
+            .crit(self.check.critical)
+            .static_tg_count_warn(self.check.static_tg_count_warn)
+            .static_tg_count_crit(self.check.static_tg_count_crit)
+            .max_position_drift(self.check.max_position_drift)
+            .metrics(self.check.metrics.iter().copied())
+            .samples(self.check.samples)
+            .escalate_after(self.check.escalate_after)
+            .max_clock_skew(self.check.max_clock_skew)
+            .max_reconnects(self.check.max_reconnects)
+            .future_timestamp(self.check.future_timestamp)
+            .network(self.check.network)
+            .check_duplicates(self.check.check_duplicates)
+            .max_response_size(self.check.max_response_size)
+            .strict_schema(self.check.strict_schema);
+        if let (Some(call), Some(key)) = (&self.check.aprs_call, &self.check.aprs_apikey) {
+            builder = builder.aprs(call, key);
+        }
+        if let Some(pattern) = &self.check.mmdvm_log {
+            builder = builder.mmdvm_log(pattern);
+        }
+        if let Some(dir) = &self.check.cache_dir {
+            builder = builder.cache(dir, self.check.cache_window);
+        }
+        if let Some((lat, lon)) = self.check.expected_position {
+            builder = builder.expected_position(lat, lon);
+        }
+        for (name, value) in &self.check.expect_field {
+            builder = builder.expect_field(name, value);
+        }
+        if let Some(policy) = self.check.retry_policy {
+            builder = builder.retry(policy);
+        }
+        if let Some(url) = api_url {
+            builder = builder.api_url(url);
+        }
+        if let Some(repeater) = self.repeater()? {
+            builder = builder.repeater(repeater);
+        }
+        if let Some(dir) = &self.check.state_dir {
+            builder = builder.state_dir(dir);
+        }
+        if self.check.debug_timing {
+            builder = builder.timings(self.timings.clone());
+        }
+        builder.build()
+    }
+}
+
+/// Parse a duration such as `90s`, `10m` or `2h30m`, a plain number being in seconds.
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    duration::parse(s, Duration::seconds(1))
+}
+
+/// Parse a duration such as `500ms` or `2s`, a plain number being in milliseconds.
+fn parse_millis(s: &str) -> Result<Duration, String> {
+    duration::parse(s, Duration::milliseconds(1))
+}
+
+/// Parse a distance in meters, with an optional `m` or `km` suffix.
+fn parse_distance(s: &str) -> Result<i64, String> {
+    let (number, factor) = if let Some(km) = s.strip_suffix("km") {
+        (km, 1000.0)
+    } else {
+        (s.strip_suffix('m').unwrap_or(s), 1.0)
+    };
+    let meters = number
+        .trim()
+        .parse::<f64>()
+        .map(|value| (value * factor).round())
+        .ok()
+        .filter(|meters| meters.abs() < i64::MAX as f64)
+        .ok_or_else(|| format!("invalid distance {}, expected e.g. 500m or 2km", s))?;
+    Ok(meters as i64)
+}
+
+/// Parse a size in bytes, with an optional `k` or `M` suffix for KiB or MiB.
+fn parse_size(s: &str) -> Result<usize, String> {
+    let (number, factor) = if let Some(k) = s.strip_suffix('k') {
+        (k, 1 << 10)
+    } else if let Some(m) = s.strip_suffix('M') {
+        (m, 1 << 20)
+    } else {
+        (s, 1)
+    };
+    number
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(factor))
+        .ok_or_else(|| format!("invalid size {}, expected e.g. 512k or 4M", s))
+}
+
+/// Parse a secret given as is, or as `env:NAME` or `file:PATH` to read it from the environment
+/// variable NAME or the file at PATH, keeping it out of the command line and process list.
+fn parse_secret(s: &str) -> Result<String, String> {
+    let secret = if let Some(name) = s.strip_prefix("env:") {
+        std::env::var(name).map_err(|_| format!("environment variable {} is not set", name))?
+    } else if let Some(path) = s.strip_prefix("file:") {
+        std::fs::read_to_string(path).map_err(|e| format!("reading {}: {}", path, e))?
+    } else {
+        return Ok(s.to_string());
+    };
+    match secret.trim() {
+        "" => Err(format!("{} is empty", s)),
+        secret => Ok(secret.to_string()),
+    }
+}
+
+/// Parse a `latitude,longitude` pair in degrees.
+fn parse_position(s: &str) -> Result<(f64, f64), String> {
+    let invalid = || format!("invalid position {}, expected e.g. 50.63,5.57", s);
+    let (lat, lon) = s.split_once(',').ok_or_else(invalid)?;
+    let lat = lat.trim().parse().map_err(|_| invalid())?;
+    let lon = lon.trim().parse().map_err(|_| invalid())?;
+    Ok((lat, lon))
+}
+
+/// Parse a `name=value` field assertion.
+fn parse_field(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.to_string()))
+        }
+        _ => Err(format!("invalid field {}, expected e.g. hardware=MMDVM", s)),
+    }
+}
+
+/// Parse a host pattern, which must hold a single `{id}`.
+fn parse_host_pattern(s: &str) -> Result<String, String> {
+    match s.matches("{id}").count() {
+        1 => Ok(s.to_string()),
+        _ => Err(format!("invalid host pattern {}, expected one {{id}}", s)),
+    }
+}
+
+/// The repeater id in `host` at the `{id}` of `pattern`, where `*` matches any characters,
+/// ignoring case.
+fn host_repeater(pattern: &str, host: &str) -> Option<u32> {
+    let pattern = pattern.to_ascii_lowercase();
+    let host = host.to_ascii_lowercase();
+    let (prefix, suffix) = pattern.split_once("{id}")?;
+    (0..=host.len())
+        .filter(|&start| host.is_char_boundary(start) && wildcard_match(prefix, &host[..start]))
+        .find_map(|start| {
+            let digits = host[start..].bytes().take_while(u8::is_ascii_digit).count();
+            let len = (1..=digits)
+                .rev()
+                .find(|&len| wildcard_match(suffix, &host[start + len..]))?;
+            host[start..start + len].parse().ok()
+        })
+}
+
+/// Reduce a clap error to its first paragraph on a single line, without usage and tips.
+fn clap_error_summary(e: &clap::Error) -> String {
+    let rendered = e.to_string();
+    let summary: Vec<&str> = rendered
+        .lines()
+        .take_while(|line| !line.trim().is_empty())
+        .map(str::trim)
+        .collect();
+    summary.join(" ").trim_start_matches("error: ").to_string()
+}
+
+/// Number of HTTP requests made by the checks and their pushes, for the --summary of --stdin
+/// runs.
+static API_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+/// Wait until the requests counted in [API_CALLS] since `start` average at most `per_minute`
+/// a minute.
+fn pace(start: Instant, per_minute: u32) {
+    let calls = API_CALLS.load(Ordering::Relaxed) as f64;
+    let budget = std::time::Duration::from_secs_f64(calls * 60.0 / f64::from(per_minute));
+    if let Some(wait) = (start + budget).checked_duration_since(Instant::now()) {
+        std::thread::sleep(wait);
+    }
+}
+
+/// Transport counting the requests it makes in [API_CALLS].
+#[derive(Debug)]
+struct CountingTransport(Arc<dyn Transport>);
+
+impl Transport for CountingTransport {
+    fn get(&self, url: &str, headers: &[(&str, &str)]) -> brandmeister::Result<HttpResponse> {
+        API_CALLS.fetch_add(1, Ordering::Relaxed);
+        self.0.get(url, headers)
+    }
+
+    fn get_limited(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        limit: usize,
+    ) -> brandmeister::Result<HttpResponse> {
+        API_CALLS.fetch_add(1, Ordering::Relaxed);
+        self.0.get_limited(url, headers, limit)
+    }
+
+    fn put(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: &str,
+    ) -> brandmeister::Result<HttpResponse> {
+        API_CALLS.fetch_add(1, Ordering::Relaxed);
+        self.0.put(url, headers, body)
+    }
+}
+
+/// Parse the name of an output format.
+fn parse_output(s: &str) -> Result<Arc<dyn OutputFormatter>, String> {
+    output::formatter(s).ok_or_else(|| {
+        format!(
+            "invalid output format {}, expected one of {}",
+            s,
+            output::FORMATS.join(", ")
+        )
+    })
+}
+
+/// Print the result of an export or snapshot and exit, with UNKNOWN if it failed.
+fn print_exported(exported: brandmeister::Result<String>) -> ! {
+    match exported {
+        Ok(exported) => print!("{}", exported),
+        Err(e) => {
+            println!("{}: {}", ServiceState::Unknown, e);
+            std::process::exit(ServiceState::Unknown.exit_code());
+        }
+    }
+    std::process::exit(0);
+}
+
+fn exit_invalid_arguments(message: &str) -> ! {
+    println!("{}: invalid arguments: {}", ServiceState::Unknown, message);
+    std::process::exit(ServiceState::Unknown.exit_code());
+}
+
+/// A line of the --pretty output.
+struct PrettyRow {
+    state: NagiosState,
+    name: String,
+    /// Time since the repeater was last seen, if known
+    seen: String,
+    /// Description and messages
+    details: String,
+}
+
+impl PrettyRow {
+    fn new(outcome: &CheckOutcome) -> Self {
+        let seen = outcome.last_seen.map_or_else(String::new, |at| {
+            let elapsed = Utc::now().signed_duration_since(at);
+            format!("seen {} ago", duration::format_duration(elapsed))
+        });
+        let mut details = vec![outcome.description.as_str()];
+        details.extend(outcome.messages.iter().map(String::as_str));
+        PrettyRow {
+            state: outcome.state,
+            name: outcome.name.clone(),
+            seen,
+            details: details.join("; "),
+        }
+    }
+}
+
+/// Render `rows` aligned in columns, with the state coloured.
+fn pretty_output(rows: &[PrettyRow]) -> String {
+    let width = |column: fn(&PrettyRow) -> &str| {
+        rows.iter()
+            .map(|row| column(row).chars().count())
+            .max()
+            .unwrap_or_default()
+    };
+    let name_width = width(|row| &row.name);
+    let seen_width = width(|row| &row.seen);
+    let lines: Vec<String> = rows
+        .iter()
+        .map(|row| {
+            let colour = match row.state {
+                NagiosState::Ok => "32",
+                NagiosState::Warning => "33",
+                NagiosState::Critical => "31",
+                NagiosState::Unknown => "35",
+            };
+            let line = format!(
+                "\x1b[1;{}m{:<8}\x1b[0m  {:<name_width$}  {:<seen_width$}  {}",
+                colour,
+                row.state.to_string(),
+                row.name,
+                row.seen,
+                sanitize_text(&row.details),
+            );
+            line.trim_end().to_string()
+        })
+        .collect();
+    lines.join("\n")
+}
+
+/// Render the outcome as the minutes since the repeater was last seen, -1 if unknown.
+fn quiet_output(outcome: &CheckOutcome) -> i64 {
+    match (&outcome.error, outcome.elapsed) {
+        (None, Some(elapsed)) => elapsed.num_minutes(),
+        _ => -1,
+    }
+}
+
+/// Join the non-empty lines of `output`, so that each check of a bulk run prints one line.
+fn single_line(output: &str) -> String {
+    let lines: Vec<&str> = output
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect();
+    lines.join("; ")
+}
+
+/// Render the outcome as a single terse line for container health probes.
+fn probe_output(outcome: &CheckOutcome, healthy: bool) -> String {
+    let health = if healthy { "healthy" } else { "unhealthy" };
+    match &outcome.error {
+        Some(error) => format!(
+            "{}: {}: {}",
+            health,
+            outcome.name,
+            sanitize_text(&error.message)
+        ),
+        None => {
+            let metrics: Vec<String> = outcome
+                .perfdata
+                .iter()
+                .map(|p| format!("{}={}{}", p.name, output::format_number(p.value), p.unit))
+                .collect();
+            format!(
+                "{}: {} is {} {}",
+                health,
+                outcome.name,
+                outcome.state,
+                metrics.join(" ")
+            )
+        }
+    }
 }
 
 fn main() {
-    Runner::new()
-        .on_error(|e| (ServiceState::Unknown, e))
-        .safe_run(do_check)
-        .print_and_exit();
+    let args = Args::parse_or_exit();
+    if args.check.stdin {
+        if args.command.is_some() {
+            exit_invalid_arguments("--stdin cannot be used with a subcommand");
+        }
+        let state = args.run_stdin();
+        args.print_timings();
+        std::process::exit(state.exit_code());
+    }
+    if args.check.max_requests_per_minute.is_some() {
+        // Clap takes the default value of --stdin as given, so `requires` cannot be used.
+        exit_invalid_arguments("--max-requests-per-minute requires --stdin");
+    }
+    if args.check.quiet && args.command.is_some() {
+        exit_invalid_arguments("--quiet cannot be used with a subcommand");
+    }
+    let outcome = args.run();
+    args.print_timings();
+    if args.probe {
+        let healthy = outcome.state == NagiosState::Ok;
+        println!("{}", probe_output(&outcome, healthy));
+        std::process::exit(if healthy { 0 } else { 1 });
+    }
+    if args.check.quiet {
+        println!("{}", quiet_output(&outcome));
+        std::process::exit(outcome.state.exit_code());
+    }
+    println!("{}", args.render(&outcome));
+    std::process::exit(outcome.state.exit_code());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn host_repeater_exact() {
+        assert_eq!(host_repeater("bm-{id}", "bm-270107"), Some(270107));
+        assert_eq!(host_repeater("bm-{id}", "BM-270107"), Some(270107));
+    }
+
+    #[test]
+    fn host_repeater_wildcards() {
+        assert_eq!(
+            host_repeater("bm-{id}.*", "bm-270107.example.org"),
+            Some(270107)
+        );
+        assert_eq!(host_repeater("*-{id}", "on0abc-270107"), Some(270107));
+        assert_eq!(host_repeater("*{id}*", "repeater270107a"), Some(270107));
+    }
+
+    #[test]
+    fn host_repeater_no_match() {
+        assert_eq!(host_repeater("bm-{id}", "dmr-270107"), None);
+        assert_eq!(host_repeater("bm-{id}", "bm-270107.example.org"), None);
+    }
+
+    #[test]
+    fn host_repeater_non_numeric_id() {
+        assert_eq!(host_repeater("bm-{id}", "bm-on0abc"), None);
+        assert_eq!(host_repeater("bm-{id}.*", "bm-.example.org"), None);
+    }
+
+    /// Summary of the error parsing the command line `check_brandmeister` followed by `args`.
+    fn clap_error(args: &[&str]) -> String {
+        let args = std::iter::once("check_brandmeister").chain(args.iter().copied());
+        clap_error_summary(&Args::try_parse_from(args).unwrap_err())
+    }
+
+    #[test]
+    fn seconds_with_units() {
+        assert_eq!(parse_seconds("90"), Ok(Duration::seconds(90)));
+        assert_eq!(parse_seconds("90s"), Ok(Duration::seconds(90)));
+        assert_eq!(parse_seconds("2h30m"), Ok(Duration::minutes(150)));
+        assert_eq!(parse_seconds("1d"), Ok(Duration::days(1)));
+        assert_eq!(parse_seconds("1500ms"), Ok(Duration::milliseconds(1500)));
+        assert_eq!(parse_seconds("0"), Ok(Duration::zero()));
+        assert_eq!(parse_seconds("0s"), Ok(Duration::zero()));
+    }
+
+    #[test]
+    fn millis_with_units() {
+        assert_eq!(parse_millis("500"), Ok(Duration::milliseconds(500)));
+        assert_eq!(parse_millis("2s"), Ok(Duration::seconds(2)));
+        assert_eq!(parse_millis("0"), Ok(Duration::zero()));
+    }
+
+    #[test]
+    fn invalid_durations() {
+        for s in [
+            "",
+            "-5",
+            "-5m",
+            "10x",
+            "m",
+            "5 m",
+            "1.5s",
+            "99999999999999999999s",
+        ] {
+            let expected = format!("invalid duration {}, expected e.g. 90s, 10m or 2h30m", s);
+            assert_eq!(parse_seconds(s), Err(expected.clone()), "{:?}", s);
+            assert_eq!(parse_millis(s), Err(expected), "{:?}", s);
+        }
+        assert!(parse_seconds("9999999999999d").is_err());
+        assert!(parse_seconds(&u32::MAX.to_string()).is_ok());
+    }
+
+    #[test]
+    fn invalid_duration_reaches_clap() {
+        let summary = clap_error(&["-r", "270107", "-w", "10x"]);
+        assert!(
+            summary.ends_with(": invalid duration 10x, expected e.g. 90s, 10m or 2h30m"),
+            "{}",
+            summary
+        );
+        assert!(!summary.contains('\n'), "{}", summary);
+        let summary = clap_error(&["-r", "270107", "--cache-window=-1"]);
+        assert!(summary.contains("invalid duration -1"), "{}", summary);
+    }
+
+    #[test]
+    fn sizes_with_units() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("512k"), Ok(512 << 10));
+        assert_eq!(parse_size("4M"), Ok(4 << 20));
+        assert_eq!(parse_size("0"), Ok(0));
+        assert_eq!(parse_size("0M"), Ok(0));
+    }
+
+    #[test]
+    fn invalid_sizes() {
+        let overflow = format!("{}k", usize::MAX);
+        for s in [
+            "",
+            "-1",
+            "-1k",
+            "4G",
+            "4 MB",
+            "1.5M",
+            "k",
+            overflow.as_str(),
+        ] {
+            let expected = format!("invalid size {}, expected e.g. 512k or 4M", s);
+            assert_eq!(parse_size(s), Err(expected), "{:?}", s);
+        }
+        let summary = clap_error(&["-r", "270107", "--max-response-size", "4G"]);
+        assert!(
+            summary.ends_with(": invalid size 4G, expected e.g. 512k or 4M"),
+            "{}",
+            summary
+        );
+    }
+
+    #[test]
+    fn check_subcommand_options() {
+        let args = ["check_brandmeister", "check", "-r", "270107", "-w", "5m"];
+        let args = Args::try_parse_from(args).unwrap();
+        assert!(matches!(args.command, Some(Command::Check(_))));
+        let args = args.without_check_command();
+        assert!(args.command.is_none());
+        assert_eq!(args.check.repeater, Some(270107));
+        assert_eq!(args.check.warning, Duration::minutes(5));
+    }
+
+    #[test]
+    fn request_budget_of_stdin_runs() {
+        let args = [
+            "check_brandmeister",
+            "--stdin",
+            "--max-requests-per-minute",
+            "30",
+        ];
+        let args = Args::try_parse_from(args).unwrap();
+        assert_eq!(args.check.max_requests_per_minute, Some(30));
+        let summary = clap_error(&["--stdin", "--max-requests-per-minute", "0"]);
+        assert!(summary.contains("0 is not in 1.."), "{}", summary);
+    }
+
+    #[test]
+    fn secret_as_is() {
+        assert_eq!(parse_secret("12345.abcdef"), Ok("12345.abcdef".to_string()));
+        assert_eq!(parse_secret(""), Ok(String::new()));
+    }
+
+    #[test]
+    fn secret_from_the_environment() {
+        std::env::set_var("CHECK_BM_TEST_SECRET", " 12345.abcdef\n");
+        std::env::set_var("CHECK_BM_TEST_EMPTY", " ");
+        std::env::remove_var("CHECK_BM_TEST_UNSET");
+        assert_eq!(
+            parse_secret("env:CHECK_BM_TEST_SECRET"),
+            Ok("12345.abcdef".to_string())
+        );
+        assert_eq!(
+            parse_secret("env:CHECK_BM_TEST_EMPTY"),
+            Err("env:CHECK_BM_TEST_EMPTY is empty".to_string())
+        );
+        assert_eq!(
+            parse_secret("env:CHECK_BM_TEST_UNSET"),
+            Err("environment variable CHECK_BM_TEST_UNSET is not set".to_string())
+        );
+    }
+
+    #[test]
+    fn secret_from_a_file() {
+        let path = std::env::temp_dir().join(format!("check_bm_secret_{}", std::process::id()));
+        std::fs::write(&path, "12345.abcdef\n").unwrap();
+        let arg = format!("file:{}", path.display());
+        assert_eq!(parse_secret(&arg), Ok("12345.abcdef".to_string()));
+        std::fs::remove_file(&path).unwrap();
+        let e = parse_secret(&arg).unwrap_err();
+        assert!(
+            e.starts_with(&format!("reading {}: ", path.display())),
+            "{}",
+            e
+        );
+
+        let summary = clap_error(&["-r", "270107", "--aprs-apikey", &arg]);
+        assert!(summary.contains(&e), "{}", summary);
+    }
+
+    #[test]
+    fn distances_with_units() {
+        assert_eq!(parse_distance("500"), Ok(500));
+        assert_eq!(parse_distance("500m"), Ok(500));
+        assert_eq!(parse_distance("2km"), Ok(2000));
+        assert_eq!(parse_distance("1.5km"), Ok(1500));
+        assert_eq!(parse_distance("0"), Ok(0));
+        assert_eq!(parse_distance("0km"), Ok(0));
+    }
+
+    #[test]
+    fn invalid_distances() {
+        for s in ["", "m", "km", "2 miles", "2mi", "1e400km", "NaN", "inf"] {
+            let expected = format!("invalid distance {}, expected e.g. 500m or 2km", s);
+            assert_eq!(parse_distance(s), Err(expected), "{:?}", s);
+        }
+        let summary = clap_error(&["-r", "270107", "--max-position-drift", "2mi"]);
+        assert!(
+            summary.ends_with(": invalid distance 2mi, expected e.g. 500m or 2km"),
+            "{}",
+            summary
+        );
+    }
+
+    #[test]
+    fn positions() {
+        assert_eq!(parse_position("50.63,5.57"), Ok((50.63, 5.57)));
+        assert_eq!(parse_position("-33.9, 18.4"), Ok((-33.9, 18.4)));
+        assert_eq!(parse_position("0,0"), Ok((0.0, 0.0)));
+    }
+
+    #[test]
+    fn invalid_positions() {
+        for s in ["", "50.63", "50.63;5.57", "50.63,", "north,east", "50,5,1"] {
+            let expected = format!("invalid position {}, expected e.g. 50.63,5.57", s);
+            assert_eq!(parse_position(s), Err(expected), "{:?}", s);
+        }
+        let summary = clap_error(&["-r", "270107", "--expected-position", "50.63"]);
+        assert!(
+            summary.ends_with(": invalid position 50.63, expected e.g. 50.63,5.57"),
+            "{}",
+            summary
+        );
+    }
+
+    #[test]
+    fn positions_out_of_range() {
+        for position in ["91,5", "50,181", "NaN,5"] {
+            let args = Args::try_parse_from([
+                "check_brandmeister",
+                "-r",
+                "270107",
+                "--expected-position",
+                position,
+                "--max-position-drift",
+                "1km",
+            ])
+            .unwrap();
+            let e = args.options().unwrap_err().to_string();
+            assert!(e.starts_with("invalid position "), "{}", e);
+        }
+    }
+
+    #[test]
+    fn field_assertions() {
+        let field = |name: &str, value: &str| Ok((name.to_string(), value.to_string()));
+        assert_eq!(parse_field("hardware=MMDVM"), field("hardware", "MMDVM"));
+        assert_eq!(parse_field(" tx =439.5000"), field("tx", "439.5000"));
+        assert_eq!(parse_field("firmware="), field("firmware", ""));
+        assert_eq!(parse_field("city=a=b"), field("city", "a=b"));
+    }
+
+    #[test]
+    fn invalid_field_assertions() {
+        for s in ["", "hardware", "=MMDVM", " =MMDVM"] {
+            let expected = format!("invalid field {}, expected e.g. hardware=MMDVM", s);
+            assert_eq!(parse_field(s), Err(expected), "{:?}", s);
+        }
+        let summary = clap_error(&["-r", "270107", "--expect-field", "hardware"]);
+        assert!(
+            summary.ends_with(": invalid field hardware, expected e.g. hardware=MMDVM"),
+            "{}",
+            summary
+        );
+    }
+
+    #[test]
+    fn stdin_lines() {
+        let args = Args::try_parse_from(["check_brandmeister", "--stdin", "-w", "5m"]).unwrap();
+        let line = args.for_stdin_line("270107").unwrap();
+        assert_eq!(line.check.repeater, Some(270107));
+        assert_eq!(line.check.warning, Duration::minutes(5));
+        assert_eq!(line.check.critical, Duration::seconds(900));
+        let line = args.for_stdin_line("270107  2m\t20m").unwrap();
+        assert_eq!(line.check.warning, Duration::minutes(2));
+        assert_eq!(line.check.critical, Duration::minutes(20));
+    }
+
+    #[test]
+    fn invalid_stdin_lines() {
+        let args = Args::try_parse_from(["check_brandmeister", "--stdin"]).unwrap();
+        for (line, expected) in [
+            ("on0abc", "invalid repeater id on0abc"),
+            ("-270107", "invalid repeater id -270107"),
+            ("99999999999", "invalid repeater id 99999999999"),
+            (
+                "270107 10x",
+                "invalid duration 10x, expected e.g. 90s, 10m or 2h30m",
+            ),
+            (
+                "270107 5m -1",
+                "invalid duration -1, expected e.g. 90s, 10m or 2h30m",
+            ),
+            (
+                "270107 5m 15m 30m",
+                "unexpected 30m, expected ID [WARNING [CRITICAL]]",
+            ),
+        ] {
+            let e = args.for_stdin_line(line).err().map(|e| e.to_string());
+            assert_eq!(e.as_deref(), Some(expected), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn quiet_output_uses_the_elapsed_time_of_the_check() {
+        let e = brandmeister::Error::NotFound("test".to_string());
+        let mut outcome = CheckOutcome::failed("test".to_string(), &e);
+        assert_eq!(quiet_output(&outcome), -1);
+        outcome.error = None;
+        // Clamped by --future-timestamp clamp although last seen in the future.
+        outcome.last_seen = Some(Utc::now() + Duration::minutes(10));
+        outcome.elapsed = Some(Duration::zero());
+        assert_eq!(quiet_output(&outcome), 0);
+        outcome.elapsed = Some(Duration::seconds(150));
+        assert_eq!(quiet_output(&outcome), 2);
+    }
+
+    /// Accept one HTTP request on a local port, answer 200 and return its request line.
+    fn serve_once() -> (String, std::thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 4096];
+            while !String::from_utf8_lossy(&request).contains("\r\n\r\n") {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            let request = String::from_utf8_lossy(&request).to_string();
+            request.lines().next().unwrap_or_default().to_string()
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn derived_repeater_is_published() {
+        let (url, server) = serve_once();
+        let dir = std::env::temp_dir().join(format!("check_bm_publish_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let status = dir.join("status.json");
+        let status_arg = status.to_str().unwrap();
+        let args = Args::try_parse_from([
+            "check_brandmeister",
+            "--derive-repeater-from-host",
+            "bm-{id}",
+            "-H",
+            "bm-270107",
+            "--pushgateway-url",
+            &url,
+            "--status-file",
+            status_arg,
+        ])
+        .unwrap();
+        let repeater = args.repeater().unwrap().unwrap();
+        let e = brandmeister::Error::NotFound("test".to_string());
+        let outcome = args.publish(repeater, CheckOutcome::failed("test".to_string(), &e));
+
+        assert!(outcome.messages.is_empty(), "{:?}", outcome.messages);
+        assert!(server.join().unwrap().contains("/repeater/270107 "));
+        assert_eq!(status_file::repeaters(&status).unwrap(), vec![270107]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,157 @@
+//! File cache sharing API responses between plugin invocations within a polling cycle.
+//!
+//! Monitoring systems like LibreNMS may run several service checks for the same repeater
+//! within one poll cycle. Responses are stored per time window, and a lock file makes
+//! concurrent invocations wait for a single fetch instead of each calling the API.
+//!
+//! The last response for each key is also kept with its `ETag` and `Last-Modified`
+//! validators, so that the next window can revalidate it with a conditional request and
+//! reuse it when the API answers 304 Not Modified.
+//!
+//! Requests for a device that does not exist are remembered for [NOT_FOUND_TTL], so that a
+//! service defined with a mistyped repeater id does not query the API at every check.
+
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::lock::FileLock;
+use crate::state::write_atomic;
+use crate::{Error, Result};
+
+/// The last response for a key, with the validators sent to revalidate it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct Validated {
+    pub(crate) etag: Option<String>,
+    pub(crate) last_modified: Option<String>,
+    pub(crate) body: String,
+}
+
+/// How long a request for a device that does not exist is answered from the cache.
+pub(crate) const NOT_FOUND_TTL: Duration = Duration::minutes(10);
+
+/// A directory of cached API responses, keyed by request and time window.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: PathBuf,
+    window: i64,
+}
+
+impl Cache {
+    /// Cache responses in `dir` for windows of `window_seconds`.
+    pub fn new(dir: impl Into<PathBuf>, window_seconds: i64) -> Self {
+        Cache {
+            dir: dir.into(),
+            window: window_seconds.max(1),
+        }
+    }
+
+    /// Cache responses in `dir` for windows of `window`, in whole seconds.
+    pub fn with_window(dir: impl Into<PathBuf>, window: Duration) -> Self {
+        Cache::new(dir, window.num_seconds())
+    }
+
+    /// Return the cached response for `key` in the current window, or call `fetch` to get it.
+    ///
+    /// Only one caller fetches a given key per window; the others wait for its result.
+    /// Errors returned by `fetch` are not cached.
+    pub fn get_or_fetch(
+        &self,
+        key: &str,
+        fetch: impl FnOnce() -> Result<String>,
+    ) -> Result<String> {
+        fs::create_dir_all(&self.dir).map_err(|e| {
+            Error::io(
+                format!("creating cache directory {}", self.dir.display()),
+                e,
+            )
+        })?;
+        let window_start = Utc::now().timestamp() / self.window * self.window;
+        let entry = self.dir.join(format!("{}-{}.json", key, window_start));
+        let lock_path = self.dir.join(format!("{}-{}.lock", key, window_start));
+
+        let lock = FileLock::acquire(&lock_path, || entry.exists())?;
+        // Another invocation may have written the entry while we waited for the lock.
+        let result = match fs::read_to_string(&entry) {
+            Ok(body) => Ok(body),
+            Err(_) => fetch().and_then(|body| {
+                write_atomic(&entry, &body)?;
+                Ok(body)
+            }),
+        };
+        drop(lock);
+        self.remove_stale(key, window_start);
+        result
+    }
+
+    /// Path of the last response for `key`, kept across windows.
+    fn validated_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.validated.json", key))
+    }
+
+    /// Return the last response stored for `key` with its validators, if any.
+    pub(crate) fn validated(&self, key: &str) -> Option<Validated> {
+        let content = fs::read_to_string(self.validated_path(key)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// Store `response` as the last response for `key`.
+    pub(crate) fn store_validated(&self, key: &str, response: &Validated) -> Result<()> {
+        let content = serde_json::to_string(response)
+            .map_err(|e| Error::parse("cached response", e.to_string()))?;
+        write_atomic(&self.validated_path(key), &content)
+    }
+
+    /// Path of the marker recording when `key` was last not found.
+    fn not_found_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.not-found", key))
+    }
+
+    /// Return how long ago `key` was not found, if within [NOT_FOUND_TTL].
+    pub(crate) fn not_found(&self, key: &str) -> Option<Duration> {
+        let content = fs::read_to_string(self.not_found_path(key)).ok()?;
+        let age = Utc::now().timestamp() - content.trim().parse::<i64>().ok()?;
+        Some(Duration::seconds(age)).filter(|age| *age < NOT_FOUND_TTL)
+    }
+
+    /// Record that `key` was not found, or that it exists again.
+    pub(crate) fn store_not_found(&self, key: &str, not_found: bool) -> Result<()> {
+        let path = self.not_found_path(key);
+        if not_found {
+            fs::create_dir_all(&self.dir).map_err(|e| {
+                Error::io(
+                    format!("creating cache directory {}", self.dir.display()),
+                    e,
+                )
+            })?;
+            write_atomic(&path, &Utc::now().timestamp().to_string())
+        } else if path.exists() {
+            fs::remove_file(&path).map_err(|e| Error::io(format!("removing {}", path.display()), e))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Remove entries and abandoned locks for `key` from previous windows.
+    fn remove_stale(&self, key: &str, window_start: i64) {
+        let prefix = format!("{}-", key);
+        let current = window_start.to_string();
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            let name = entry.file_name();
+            let window = name
+                .to_str()
+                .and_then(|n| n.strip_prefix(&prefix))
+                .and_then(|n| n.strip_suffix(".json").or_else(|| n.strip_suffix(".lock")));
+            if let Some(window) = window {
+                if window != current && window.bytes().all(|b| b.is_ascii_digit()) {
+                    let _ = fs::remove_file(entry.path());
+                }
+            }
+        }
+    }
+}
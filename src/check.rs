@@ -0,0 +1,2008 @@
+//! High-level check of a repeater, as done by the check_brandmeister plugin.
+//!
+//! [check] runs every configured measurement and evaluates the thresholds, so that other
+//! monitoring agents can embed the same logic as the plugin and only render the outcome.
+//! [check_api] checks the BrandMeister API itself, and [check_master_fleet] the repeaters of a
+//! master server as a whole. [selftest] verifies the environment the plugin runs in.
+
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::client::{API_URL, MAX_RESPONSE_SIZE};
+use crate::duration;
+use crate::network::{Network, NetworkProvider};
+use crate::output::{format_number, sanitize_label};
+use crate::thresholds::{self, Range};
+use crate::transport::encode_query;
+use crate::{
+    aprs, mmdvm, BmClient, Cache, Error, RepeaterMetadata, Result, RetryPolicy, StateDir, Timings,
+    Transport,
+};
+
+mod api;
+mod drift;
+mod fleet;
+mod selftest;
+
+pub use api::{check_api, ApiCheckOptions, ApiCheckOptionsBuilder};
+pub use drift::{check_drift, DriftCheckOptions, DriftCheckOptionsBuilder};
+pub use fleet::{check_master_fleet, FleetCheckOptions, FleetCheckOptionsBuilder};
+pub use selftest::{selftest, SelftestOptions};
+
+/// State of a check, as defined by the nagios plugin guidelines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum NagiosState {
+    /// Everything is fine
+    Ok,
+    /// A warning threshold was reached
+    Warning,
+    /// A critical threshold was reached
+    Critical,
+    /// The check could not be performed
+    Unknown,
+}
+
+impl NagiosState {
+    /// Exit code of a plugin reporting this state.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            NagiosState::Ok => 0,
+            NagiosState::Warning => 1,
+            NagiosState::Critical => 2,
+            NagiosState::Unknown => 3,
+        }
+    }
+
+    /// Ordering from best to worst: Ok < Unknown < Warning < Critical.
+    fn severity(&self) -> u8 {
+        match self {
+            NagiosState::Ok => 0,
+            NagiosState::Unknown => 1,
+            NagiosState::Warning => 2,
+            NagiosState::Critical => 3,
+        }
+    }
+
+    /// Return the worst of the two states.
+    pub fn worst(self, other: NagiosState) -> NagiosState {
+        if other.severity() > self.severity() {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+impl fmt::Display for NagiosState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            NagiosState::Ok => "OK",
+            NagiosState::Warning => "WARNING",
+            NagiosState::Critical => "CRITICAL",
+            NagiosState::Unknown => "UNKNOWN",
+        })
+    }
+}
+
+impl FromStr for NagiosState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "OK" => Ok(NagiosState::Ok),
+            "WARNING" => Ok(NagiosState::Warning),
+            "CRITICAL" => Ok(NagiosState::Critical),
+            "UNKNOWN" => Ok(NagiosState::Unknown),
+            _ => Err(format!("invalid state {}", s)),
+        }
+    }
+}
+
+/// A metric value compared to optional warning and critical thresholds.
+#[derive(Debug, Clone, Serialize)]
+pub struct PerfData {
+    /// Label of the metric
+    pub name: String,
+    /// Measured value
+    pub value: f64,
+    /// Unit of measure, `s`, `ms`, `%`, `m` or empty
+    pub unit: String,
+    /// Warning threshold
+    pub warning: Option<f64>,
+    /// Critical threshold
+    pub critical: Option<f64>,
+    /// Minimum possible value
+    pub minimum: Option<f64>,
+    /// Whether the thresholds trigger on low instead of high values
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub low_is_bad: bool,
+}
+
+impl PerfData {
+    /// An elapsed time that triggers when at or above the thresholds.
+    pub fn seconds(name: &str, value: i64, warning: i64, critical: i64) -> Self {
+        PerfData {
+            name: name.to_string(),
+            value: value as f64,
+            unit: "s".to_string(),
+            warning: Some(warning as f64),
+            critical: Some(critical as f64),
+            minimum: Some(0.0),
+            low_is_bad: false,
+        }
+    }
+
+    /// An elapsed time in whole seconds that triggers when at or above the thresholds.
+    pub fn duration(name: &str, value: Duration, warning: Duration, critical: Duration) -> Self {
+        PerfData::seconds(
+            name,
+            value.num_seconds(),
+            warning.num_seconds(),
+            critical.num_seconds(),
+        )
+    }
+
+    /// An elapsed time in fractional minutes without unit, as older dashboards expect, that
+    /// triggers when at or above the thresholds.
+    pub fn minutes(name: &str, value: Duration, warning: Duration, critical: Duration) -> Self {
+        let minutes = |d: Duration| d.num_milliseconds() as f64 / 60_000.0;
+        PerfData {
+            value: minutes(value),
+            unit: String::new(),
+            warning: Some(minutes(warning)),
+            critical: Some(minutes(critical)),
+            ..PerfData::seconds(name, 0, 0, 0)
+        }
+    }
+
+    /// A response time that triggers when at or above the thresholds.
+    pub fn milliseconds(name: &str, value: i64, warning: i64, critical: i64) -> Self {
+        PerfData {
+            unit: "ms".to_string(),
+            ..PerfData::seconds(name, value, warning, critical)
+        }
+    }
+
+    /// A percentage that triggers when at or above the thresholds.
+    pub fn percent(name: &str, value: i64, warning: i64, critical: i64) -> Self {
+        PerfData {
+            unit: "%".to_string(),
+            ..PerfData::seconds(name, value, warning, critical)
+        }
+    }
+
+    /// A distance in meters that triggers a warning when at or above `warning`.
+    pub fn meters(name: &str, value: i64, warning: i64) -> Self {
+        PerfData {
+            unit: "m".to_string(),
+            critical: None,
+            ..PerfData::seconds(name, value, warning, 0)
+        }
+    }
+
+    /// A count without thresholds.
+    pub fn count(name: &str, value: i64) -> Self {
+        PerfData {
+            low_is_bad: false,
+            ..PerfData::minimum_count(name, value, None, None)
+        }
+    }
+
+    /// A count that triggers when at or below the thresholds.
+    pub fn minimum_count(
+        name: &str,
+        value: i64,
+        warning: Option<i64>,
+        critical: Option<i64>,
+    ) -> Self {
+        PerfData {
+            name: name.to_string(),
+            value: value as f64,
+            unit: String::new(),
+            warning: warning.map(|w| w as f64),
+            critical: critical.map(|c| c as f64),
+            minimum: Some(0.0),
+            low_is_bad: true,
+        }
+    }
+
+    /// State of the metric according to its thresholds.
+    pub fn state(&self) -> NagiosState {
+        let range = |threshold: f64| {
+            if self.low_is_bad {
+                Range::at_or_below(threshold)
+            } else {
+                Range::at_or_above(threshold)
+            }
+        };
+        thresholds::evaluate(
+            self.value,
+            self.warning.map(range).as_ref(),
+            self.critical.map(range).as_ref(),
+        )
+    }
+
+    /// Render `threshold` as a Nagios range: a bare number alerts above it, which suits the
+    /// time and count thresholds, while the thresholds of low-is-bad metrics alert at or
+    /// below it, e.g. `@~:5`.
+    pub fn threshold_range(&self, threshold: f64) -> String {
+        if self.low_is_bad {
+            Range::at_or_below(threshold).to_string()
+        } else {
+            format_number(threshold)
+        }
+    }
+
+    /// Explain which threshold was exceeded, if any.
+    fn message(&self) -> Option<String> {
+        let (state, threshold) = match self.state() {
+            NagiosState::Critical => (NagiosState::Critical, self.critical?),
+            NagiosState::Warning => (NagiosState::Warning, self.warning?),
+            _ => return None,
+        };
+        let verb = if self.low_is_bad {
+            "has dropped to"
+        } else {
+            "has exceeded"
+        };
+        Some(format!(
+            "metric '{}' is {}: value '{}' {} threshold of '{}'",
+            self.name,
+            state,
+            format_number(self.value),
+            verb,
+            format_number(threshold)
+        ))
+    }
+}
+
+impl fmt::Display for PerfData {
+    /// Format as nagios performance data, e.g. `'last_seen'=152s;600;900;0;`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let threshold = |t: Option<f64>| t.map(|t| self.threshold_range(t)).unwrap_or_default();
+        write!(
+            f,
+            "'{}'={}{};{};{};{};",
+            sanitize_label(&self.name),
+            format_number(self.value),
+            self.unit,
+            threshold(self.warning),
+            threshold(self.critical),
+            self.minimum.map(format_number).unwrap_or_default()
+        )
+    }
+}
+
+/// Optional metric reported by a repeater check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Metric {
+    /// Seconds since the repeater was last seen, `last_seen`
+    LastSeen,
+    /// Minutes since the repeater was last seen, `last_seen_min`
+    LastSeenMinutes,
+    /// Status code reported by the API, `status_code`
+    StatusCode,
+    /// Number of linked static talkgroups, `linked_static_tg`
+    LinkedStaticTalkgroups,
+    /// Number of linked dynamic talkgroups, `linked_dynamic_tg`
+    LinkedDynamicTalkgroups,
+    /// Response time of the API in milliseconds, `api_rtt_ms`
+    ApiResponseTime,
+}
+
+impl Metric {
+    /// Perfdata label of the metric.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Metric::LastSeen => "last_seen",
+            Metric::LastSeenMinutes => "last_seen_min",
+            Metric::StatusCode => "status_code",
+            Metric::LinkedStaticTalkgroups => "linked_static_tg",
+            Metric::LinkedDynamicTalkgroups => "linked_dynamic_tg",
+            Metric::ApiResponseTime => "api_rtt_ms",
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            Metric::LastSeen,
+            Metric::LastSeenMinutes,
+            Metric::StatusCode,
+            Metric::LinkedStaticTalkgroups,
+            Metric::LinkedDynamicTalkgroups,
+            Metric::ApiResponseTime,
+        ]
+        .into_iter()
+        .find(|m| m.name() == s)
+        .ok_or_else(|| format!("invalid metric {}", s))
+    }
+}
+
+/// How a last seen time in the future of the local clock is reported.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FutureTimestamp {
+    /// Report the negative elapsed time as is, `ok`
+    Ok,
+    /// Report UNKNOWN, as the local or API clock is wrong, `unknown`
+    Unknown,
+    /// Report the repeater as seen just now, `clamp`
+    #[default]
+    Clamp,
+}
+
+impl FutureTimestamp {
+    /// Name of the policy on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FutureTimestamp::Ok => "ok",
+            FutureTimestamp::Unknown => "unknown",
+            FutureTimestamp::Clamp => "clamp",
+        }
+    }
+}
+
+impl FromStr for FutureTimestamp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            FutureTimestamp::Ok,
+            FutureTimestamp::Unknown,
+            FutureTimestamp::Clamp,
+        ]
+        .into_iter()
+        .find(|p| p.name() == s)
+        .ok_or_else(|| format!("invalid future timestamp policy {}", s))
+    }
+}
+
+/// Machine-readable description of a failed check.
+#[derive(Debug, Clone, Serialize)]
+pub struct Failure {
+    /// Kind of failure, see [Error::category]
+    pub category: &'static str,
+    /// HTTP status returned by the API, if any
+    pub http_status: Option<u16>,
+    /// Whether running the check again later may succeed
+    pub retryable: bool,
+    /// Human readable error chain
+    pub message: String,
+}
+
+impl From<&Error> for Failure {
+    fn from(e: &Error) -> Self {
+        Failure {
+            category: e.category(),
+            http_status: e.http_status(),
+            retryable: e.is_retryable(),
+            message: e.to_string(),
+        }
+    }
+}
+
+/// Configuration of a repeater check, created with [CheckOptions::builder].
+///
+/// Example:
+/// ```no_run
+/// use brandmeister::check::{check, CheckOptions};
+/// use chrono::Duration;
+/// let opts = CheckOptions::builder()
+///     .repeater(270107)
+///     .warn(Duration::minutes(10))
+///     .crit(Duration::minutes(30))
+///     .build()
+///     .unwrap();
+/// let outcome = check(&opts);
+/// println!("{}: {}", outcome.state, outcome.description);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CheckOptions {
+    repeater: u32,
+    warning: Duration,
+    critical: Duration,
+    aprs_call: Option<String>,
+    aprs_apikey: Option<String>,
+    mmdvm_log: Option<String>,
+    cache_dir: Option<PathBuf>,
+    cache_window: Duration,
+    static_tg_count_warn: Option<i64>,
+    static_tg_count_crit: Option<i64>,
+    max_position_drift: Option<i64>,
+    expected_position: Option<(f64, f64)>,
+    state_dir: Option<PathBuf>,
+    samples: u32,
+    escalate_after: Option<Duration>,
+    max_clock_skew: Option<Duration>,
+    max_reconnects: Option<u32>,
+    future_timestamp: FutureTimestamp,
+    network: Network,
+    check_duplicates: bool,
+    expected_fields: Vec<(String, String)>,
+    metrics: Vec<Metric>,
+    retry: RetryPolicy,
+    api_url: String,
+    max_response_size: usize,
+    strict_schema: bool,
+    timings: Option<Arc<Timings>>,
+    transport: Option<Arc<dyn Transport>>,
+}
+
+impl CheckOptions {
+    /// Start configuring a check with the plugin's default thresholds.
+    pub fn builder() -> CheckOptionsBuilder {
+        CheckOptionsBuilder::default()
+    }
+
+    /// ID of the BrandMeister repeater to check.
+    pub fn repeater(&self) -> u32 {
+        self.repeater
+    }
+
+    fn client(&self) -> Result<BmClient> {
+        let mut client = match &self.transport {
+            Some(transport) => BmClient::with_transport(transport.clone()),
+            None => BmClient::with_default_transport()?,
+        };
+        if let Some(dir) = &self.cache_dir {
+            client = client.with_cache(Cache::with_window(dir, self.cache_window));
+        }
+        if let Some(timings) = &self.timings {
+            client = client.with_timings(timings.clone());
+        }
+        // The device accessors of one check share the device record instead of each
+        // fetching it.
+        Ok(client
+            .with_device_reuse()
+            .with_retry(self.retry)
+            .with_api_url(&self.api_url)
+            .with_max_response_size(self.max_response_size)
+            .with_strict_schema(self.strict_schema))
+    }
+
+    /// Provider of the network the repeater is checked on, querying `client` for BrandMeister.
+    fn provider<'a>(&self, client: &'a BmClient) -> &'a dyn NetworkProvider {
+        match self.network {
+            Network::Brandmeister => client,
+        }
+    }
+
+    /// Describe the resolved configuration, the API requests a check would make and how the
+    /// thresholds are evaluated, without making any request.
+    pub fn dry_run(&self) -> String {
+        let mut lines = vec![
+            format!("repeater: {}", self.repeater),
+            format!("network: {}", self.network.name()),
+        ];
+        let mut requests = vec![format!("{}/device/{}", self.api_url, self.repeater)];
+
+        let last_seen: Vec<&str> = self
+            .metrics
+            .iter()
+            .filter(|m| matches!(m, Metric::LastSeen | Metric::LastSeenMinutes))
+            .map(Metric::name)
+            .collect();
+        lines.push(format!(
+            "{}: WARNING when at or above {}s, CRITICAL when at or above {}s",
+            last_seen.join(" and "),
+            self.warning.num_seconds(),
+            self.critical.num_seconds()
+        ));
+        let metrics: Vec<&str> = self.metrics.iter().map(Metric::name).collect();
+        lines.push(format!("metrics: {}", metrics.join(",")));
+        if self.metrics.iter().any(|m| {
+            matches!(
+                m,
+                Metric::LinkedStaticTalkgroups | Metric::LinkedDynamicTalkgroups
+            )
+        }) {
+            requests.push(format!("{}/device/{}/profile", self.api_url, self.repeater));
+        }
+        if let (Some(call), Some(key)) = (&self.aprs_call, &self.aprs_apikey) {
+            lines.push(format!(
+                "aprs_last_heard of {}: same thresholds as last_seen",
+                call
+            ));
+            requests.push(aprs::request_url(call, key).replace(&encode_query(key), "<apikey>"));
+        }
+        if let Some(pattern) = &self.mmdvm_log {
+            lines.push(format!(
+                "mmdvm_last_activity from {}: same thresholds as last_seen",
+                pattern
+            ));
+        }
+        if self.static_tg_count_warn.is_some() || self.static_tg_count_crit.is_some() {
+            let thresholds: Vec<String> = [
+                ("WARNING", self.static_tg_count_warn),
+                ("CRITICAL", self.static_tg_count_crit),
+            ]
+            .iter()
+            .filter_map(|(state, count)| Some(format!("{} when at or below {}", state, (*count)?)))
+            .collect();
+            lines.push(format!("static_tg_count: {}", thresholds.join(", ")));
+            requests.push(format!(
+                "{}/device/{}/talkgroup",
+                self.api_url, self.repeater
+            ));
+        }
+        if self.check_duplicates {
+            lines.push(
+                "duplicate_ids: WARNING when other devices seen within the critical threshold \
+                 claim the callsign"
+                    .to_string(),
+            );
+            requests.push(format!(
+                "{}/device/byCall?callsign=<callsign>",
+                self.api_url
+            ));
+        }
+        if !self.expected_fields.is_empty() {
+            let fields: Vec<String> = self
+                .expected_fields
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect();
+            lines.push(format!(
+                "unexpected_fields: WARNING when the record differs from {}",
+                fields.join(", ")
+            ));
+        }
+        if let Some(drift) = self.max_position_drift {
+            let reference = match self.expected_position {
+                Some((lat, lon)) => format!("{},{}", lat, lon),
+                None => "the first recorded position".to_string(),
+            };
+            lines.push(format!(
+                "position_drift: WARNING when at or above {}m from {}",
+                drift, reference
+            ));
+        }
+        lines.push(format!(
+            "responses: at most {} bytes, {} schema",
+            self.max_response_size,
+            if self.strict_schema {
+                "strict"
+            } else {
+                "lenient"
+            }
+        ));
+        if let Some(dir) = &self.cache_dir {
+            lines.push(format!(
+                "cache: {}, window of {}s",
+                dir.display(),
+                self.cache_window.num_seconds()
+            ));
+        }
+        if let Some(dir) = &self.state_dir {
+            lines.push(format!("state: {}", dir.display()));
+            if self.samples > 1 {
+                lines.push(format!(
+                    "samples: state changes after {} consecutive breaches",
+                    self.samples
+                ));
+            }
+            if self.metrics.contains(&Metric::ApiResponseTime) {
+                lines.push(format!(
+                    "latency: api_rtt_p50_ms and api_rtt_p95_ms over the last {} checks",
+                    LATENCY_SAMPLES
+                ));
+            }
+            if let Some(after) = self.escalate_after {
+                lines.push(format!(
+                    "escalation: CRITICAL for at least {}s is reported as a long-term outage",
+                    after.num_seconds()
+                ));
+            }
+            if let Some(count) = self.max_reconnects {
+                lines.push(format!(
+                    "reconnects: WARNING when more than {} in the last hour",
+                    count
+                ));
+            }
+        }
+        lines.push(format!(
+            "future last seen times: {}",
+            self.future_timestamp.name()
+        ));
+        if let Some(skew) = self.max_clock_skew {
+            lines.push(format!(
+                "clock: UNKNOWN when more than {}s off the API server clock",
+                skew.num_seconds()
+            ));
+        }
+        lines.push(format!("retry: {}", self.retry));
+        lines.push("requests:".to_string());
+        lines.extend(requests.iter().map(|url| format!("  GET {}", url)));
+        lines.join("\n")
+    }
+}
+
+/// BrandMeister device ids: 6 digits for repeaters, 7 to 9 digits for hotspots.
+const REPEATER_IDS: std::ops::RangeInclusive<u32> = 100_000..=999_999_999;
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::InvalidOptions(message.into())
+}
+
+/// Builder of [CheckOptions], validating the configuration in [CheckOptionsBuilder::build].
+#[derive(Debug, Clone)]
+pub struct CheckOptionsBuilder {
+    repeater: Option<u32>,
+    options: CheckOptions,
+}
+
+impl Default for CheckOptionsBuilder {
+    fn default() -> Self {
+        CheckOptionsBuilder {
+            repeater: None,
+            options: CheckOptions {
+                repeater: 0,
+                warning: Duration::minutes(10),
+                critical: Duration::minutes(15),
+                aprs_call: None,
+                aprs_apikey: None,
+                mmdvm_log: None,
+                cache_dir: None,
+                cache_window: Duration::minutes(1),
+                static_tg_count_warn: None,
+                static_tg_count_crit: None,
+                max_position_drift: None,
+                expected_position: None,
+                state_dir: None,
+                samples: 1,
+                escalate_after: None,
+                max_clock_skew: None,
+                max_reconnects: None,
+                future_timestamp: FutureTimestamp::default(),
+                network: Network::default(),
+                check_duplicates: false,
+                expected_fields: Vec::new(),
+                metrics: vec![Metric::LastSeen],
+                retry: RetryPolicy::default(),
+                api_url: API_URL.to_string(),
+                max_response_size: MAX_RESPONSE_SIZE,
+                strict_schema: false,
+                timings: None,
+                transport: None,
+            },
+        }
+    }
+}
+
+impl CheckOptionsBuilder {
+    /// ID of the BrandMeister repeater to check, required.
+    pub fn repeater(mut self, repeater: u32) -> Self {
+        self.repeater = Some(repeater);
+        self
+    }
+
+    /// Inactive time before warning state, 10 minutes by default.
+    pub fn warn(mut self, warning: Duration) -> Self {
+        self.options.warning = warning;
+        self
+    }
+
+    /// Inactive time before critical state, 15 minutes by default.
+    pub fn crit(mut self, critical: Duration) -> Self {
+        self.options.critical = critical;
+        self
+    }
+
+    /// Cross-check the APRS beacon or object `call` on aprs.fi with `api_key`.
+    pub fn aprs(mut self, call: impl Into<String>, api_key: impl Into<String>) -> Self {
+        self.options.aprs_call = Some(call.into());
+        self.options.aprs_apikey = Some(api_key.into());
+        self
+    }
+
+    /// Reconcile the API view with the MMDVMHost log file(s) matching `pattern`.
+    pub fn mmdvm_log(mut self, pattern: impl Into<String>) -> Self {
+        self.options.mmdvm_log = Some(pattern.into());
+        self
+    }
+
+    /// Share API responses in `dir` between checks within the same `window`.
+    pub fn cache(mut self, dir: impl Into<PathBuf>, window: Duration) -> Self {
+        self.options.cache_dir = Some(dir.into());
+        self.options.cache_window = window;
+        self
+    }
+
+    /// Warning when the number of static talkgroups is at or below `count`.
+    pub fn static_tg_count_warn(mut self, count: impl Into<Option<i64>>) -> Self {
+        self.options.static_tg_count_warn = count.into();
+        self
+    }
+
+    /// Critical when the number of static talkgroups is at or below `count`.
+    pub fn static_tg_count_crit(mut self, count: impl Into<Option<i64>>) -> Self {
+        self.options.static_tg_count_crit = count.into();
+        self
+    }
+
+    /// Warning when the repeater position moved more than `meters` from the expected position,
+    /// or from the first position recorded in the state directory.
+    pub fn max_position_drift(mut self, meters: impl Into<Option<i64>>) -> Self {
+        self.options.max_position_drift = meters.into();
+        self
+    }
+
+    /// Compare the repeater position with `latitude` and `longitude` in degrees.
+    pub fn expected_position(mut self, latitude: f64, longitude: f64) -> Self {
+        self.options.expected_position = Some((latitude, longitude));
+        self
+    }
+
+    /// Warn when other devices seen recently are registered with the repeater's callsign,
+    /// which may reveal a cloned or hijacked registration.
+    pub fn check_duplicates(mut self, check_duplicates: bool) -> Self {
+        self.options.check_duplicates = check_duplicates;
+        self
+    }
+
+    /// Warn when the field `name` of the repeater record is not `value`, compared as text.
+    /// Repeat to pin several fields.
+    pub fn expect_field(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.options
+            .expected_fields
+            .push((name.into(), value.into()));
+        self
+    }
+
+    /// Keep state between checks in `dir`.
+    pub fn state_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.options.state_dir = Some(dir.into());
+        self
+    }
+
+    /// Require `samples` consecutive threshold breaches before changing state.
+    pub fn samples(mut self, samples: u32) -> Self {
+        self.options.samples = samples;
+        self
+    }
+
+    /// Report a repeater CRITICAL for at least `after` as a long-term outage, to tell it apart
+    /// from fresh outages. Requires a state directory.
+    pub fn escalate_after(mut self, after: impl Into<Option<Duration>>) -> Self {
+        self.options.escalate_after = after.into();
+        self
+    }
+
+    /// Report UNKNOWN when the local clock is more than `skew` off the `Date` of the API
+    /// server, as elapsed times computed with a wrong clock would raise bogus alerts.
+    pub fn max_clock_skew(mut self, skew: impl Into<Option<Duration>>) -> Self {
+        self.options.max_clock_skew = skew.into();
+        self
+    }
+
+    /// Check the repeater on `network`, BrandMeister by default.
+    pub fn network(mut self, network: Network) -> Self {
+        self.options.network = network;
+        self
+    }
+
+    /// Warn when the repeater reconnects more than `count` times per hour, i.e. moves to
+    /// another master or comes back after being seen stale, surfacing flaky links that never
+    /// stay down long enough to breach the last seen thresholds. Requires a state directory.
+    pub fn max_reconnects(mut self, count: impl Into<Option<u32>>) -> Self {
+        self.options.max_reconnects = count.into();
+        self
+    }
+
+    /// Report a last seen time in the future of the local clock according to `policy`,
+    /// clamped to now by default so that graphs never get negative elapsed times.
+    pub fn future_timestamp(mut self, policy: FutureTimestamp) -> Self {
+        self.options.future_timestamp = policy;
+        self
+    }
+
+    /// Report `metrics` instead of only `last_seen`. The thresholds apply to `last_seen` and
+    /// `last_seen_min`, so one of them is required.
+    pub fn metrics(mut self, metrics: impl IntoIterator<Item = Metric>) -> Self {
+        self.options.metrics = metrics.into_iter().collect();
+        self
+    }
+
+    /// Retry API requests failing with a transient error according to `policy`.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.options.retry = policy;
+        self
+    }
+
+    /// Use the BrandMeister API at `url` instead of the public one, e.g. a local proxy.
+    pub fn api_url(mut self, url: impl Into<String>) -> Self {
+        self.options.api_url = url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Report API responses larger than `bytes` as UNKNOWN, 4 MiB by default.
+    pub fn max_response_size(mut self, bytes: usize) -> Self {
+        self.options.max_response_size = bytes;
+        self
+    }
+
+    /// Report API responses not declared as JSON, and device lookups answering a list, as
+    /// UNKNOWN instead of making the most of them.
+    pub fn strict_schema(mut self, strict: bool) -> Self {
+        self.options.strict_schema = strict;
+        self
+    }
+
+    /// Record the time to parse the API responses in `timings`, along with the phases of the
+    /// requests if the transport records into them too.
+    pub fn timings(mut self, timings: Arc<Timings>) -> Self {
+        self.options.timings = Some(timings);
+        self
+    }
+
+    /// Perform the HTTP requests with `transport` instead of the default one from the
+    /// `ureq` feature.
+    pub fn transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.options.transport = Some(transport);
+        self
+    }
+
+    /// Validate the configuration.
+    pub fn build(self) -> Result<CheckOptions> {
+        let mut opts = self.options;
+        opts.repeater = self
+            .repeater
+            .ok_or_else(|| invalid("repeater id is required"))?;
+        if !REPEATER_IDS.contains(&opts.repeater) {
+            return Err(invalid(format!(
+                "invalid repeater id {}, expected 6 to 9 digits",
+                opts.repeater
+            )));
+        }
+        if opts.warning < Duration::zero() || opts.critical < Duration::zero() {
+            return Err(invalid("thresholds must not be negative"));
+        }
+        if opts.warning > opts.critical {
+            return Err(invalid(format!(
+                "warning threshold {} is greater than critical threshold {}",
+                opts.warning.num_seconds(),
+                opts.critical.num_seconds()
+            )));
+        }
+        if opts.cache_window < Duration::seconds(1) {
+            return Err(invalid("cache window must be at least one second"));
+        }
+        if opts.samples < 1 {
+            return Err(invalid("samples must be at least 1"));
+        }
+        if opts.samples > 1 && opts.state_dir.is_none() {
+            return Err(invalid("samples require a state directory"));
+        }
+        if let Some(after) = opts.escalate_after {
+            if after < Duration::zero() {
+                return Err(invalid("escalation time must not be negative"));
+            }
+            if opts.state_dir.is_none() {
+                return Err(invalid("escalation requires a state directory"));
+            }
+        }
+        if opts
+            .expected_fields
+            .iter()
+            .any(|(name, _)| name.trim().is_empty())
+        {
+            return Err(invalid("expected field names must not be empty"));
+        }
+        if opts.max_reconnects.is_some() && opts.state_dir.is_none() {
+            return Err(invalid("maximum reconnects require a state directory"));
+        }
+        if opts
+            .max_clock_skew
+            .is_some_and(|skew| skew < Duration::zero())
+        {
+            return Err(invalid("clock skew must not be negative"));
+        }
+        if !opts.metrics.contains(&Metric::LastSeen)
+            && !opts.metrics.contains(&Metric::LastSeenMinutes)
+        {
+            return Err(invalid("metrics must include last_seen or last_seen_min"));
+        }
+        let tg_counts = [opts.static_tg_count_warn, opts.static_tg_count_crit];
+        if tg_counts.iter().flatten().any(|&c| c < 0) {
+            return Err(invalid("static talkgroup counts must not be negative"));
+        }
+        if let [Some(warn), Some(crit)] = tg_counts {
+            if warn < crit {
+                return Err(invalid(format!(
+                    "static talkgroup warning count {} is lower than critical count {}",
+                    warn, crit
+                )));
+            }
+        }
+        match (opts.max_position_drift, opts.expected_position) {
+            (Some(drift), _) if drift < 0 => {
+                return Err(invalid("maximum position drift must not be negative"))
+            }
+            (Some(_), None) if opts.state_dir.is_none() => {
+                return Err(invalid(
+                    "maximum position drift requires an expected position or a state directory",
+                ))
+            }
+            (None, Some(_)) => {
+                return Err(invalid(
+                    "expected position requires a maximum position drift",
+                ))
+            }
+            _ => {}
+        }
+        if let Some((lat, lon)) = opts.expected_position {
+            if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+                return Err(invalid(format!("invalid position {},{}", lat, lon)));
+            }
+        }
+        Ok(opts)
+    }
+}
+
+/// Result of a repeater check.
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckOutcome {
+    /// Overall state of the check
+    pub state: NagiosState,
+    /// Name of the checked resource, e.g. `BrandMeister repeater 270107`
+    pub name: String,
+    /// Status text
+    pub description: String,
+    /// Details on the thresholds that were exceeded
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<String>,
+    /// Measured metrics
+    pub perfdata: Vec<PerfData>,
+    /// Time the repeater was last seen, in ISO 8601 format in JSON
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<DateTime<Utc>>,
+    /// Time since the repeater was last seen as compared to the thresholds, after applying
+    /// the future timestamp policy
+    #[serde(skip)]
+    pub elapsed: Option<Duration>,
+    /// Whether the repeater has been CRITICAL for longer than the escalation time
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub escalated: bool,
+    /// What went wrong when the state is UNKNOWN because the check failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<Failure>,
+}
+
+impl CheckOutcome {
+    fn new(name: String, description: String, perfdata: Vec<PerfData>) -> Self {
+        let state = perfdata
+            .iter()
+            .fold(NagiosState::Ok, |state, p| state.worst(p.state()));
+        let messages = perfdata.iter().filter_map(PerfData::message).collect();
+        CheckOutcome {
+            state,
+            name,
+            description,
+            messages,
+            perfdata,
+            last_seen: None,
+            elapsed: None,
+            escalated: false,
+            error: None,
+        }
+    }
+
+    /// An UNKNOWN outcome reporting the failure `e` of the check `name`.
+    pub fn failed(name: String, e: &Error) -> Self {
+        let failure = Failure::from(e);
+        CheckOutcome {
+            state: NagiosState::Unknown,
+            name,
+            description: failure.message.clone(),
+            messages: Vec::new(),
+            perfdata: Vec::new(),
+            last_seen: None,
+            elapsed: None,
+            escalated: false,
+            error: Some(failure),
+        }
+    }
+}
+
+/// Check the repeater as configured by `opts`. Failures are reported as an UNKNOWN outcome.
+pub fn check(opts: &CheckOptions) -> CheckOutcome {
+    let client = opts.client();
+    let mut name = format!("BrandMeister repeater {}", opts.repeater);
+    if let Some(label) = repeater_metadata(opts, client.as_ref().ok()).and_then(|m| m.label()) {
+        name = format!("{} {}", name, label);
+    }
+    let mut outcome = match client.and_then(|client| run(opts, &client, name.clone())) {
+        Ok(outcome) => outcome,
+        Err(e) => CheckOutcome::failed(name, &e),
+    };
+    if let Some(dir) = &opts.state_dir {
+        // The transition note is only context, failing to record it must not fail the check.
+        let _ = note_transition(&mut outcome, opts, &StateDir::new(dir));
+    }
+    outcome
+}
+
+fn run(opts: &CheckOptions, client: &BmClient, name: String) -> Result<CheckOutcome> {
+    let start = Instant::now();
+    let last_seen = opts.provider(client).last_seen_at(opts.repeater)?;
+    // The record may have been fetched for the metadata already, reading it again is instant.
+    let api_rtt = client
+        .device_fetch_time(opts.repeater)
+        .unwrap_or_else(|| start.elapsed())
+        .as_millis() as i64;
+    if let (Some(max_skew), Some(server)) = (opts.max_clock_skew, client.server_date()) {
+        let skew = Utc::now().signed_duration_since(server);
+        if skew.abs() > max_skew {
+            return Err(Error::Clock(format!(
+                "local clock is {}s off the API server clock, last seen times cannot be trusted",
+                skew.num_seconds()
+            )));
+        }
+    }
+    let mut elapsed = Utc::now().signed_duration_since(last_seen);
+    if elapsed < Duration::zero() {
+        match opts.future_timestamp {
+            FutureTimestamp::Ok => {}
+            FutureTimestamp::Unknown => {
+                return Err(Error::Clock(format!(
+                    "the API reports the repeater last seen {}s in the future, check the \
+                     local clock",
+                    -elapsed.num_seconds()
+                )))
+            }
+            FutureTimestamp::Clamp => elapsed = Duration::zero(),
+        }
+    }
+    // Both talkgroup counts come from the profile, fetched once for the two metrics.
+    let mut profile_counts = None;
+    let mut linked_talkgroup_counts = || -> Result<(usize, usize)> {
+        if profile_counts.is_none() {
+            profile_counts = Some(client.linked_talkgroup_counts(opts.repeater)?);
+        }
+        Ok(profile_counts.unwrap_or_default())
+    };
+    let mut perfdata = Vec::new();
+    for metric in &opts.metrics {
+        let name = metric.name();
+        perfdata.push(match metric {
+            Metric::LastSeen => PerfData::duration(name, elapsed, opts.warning, opts.critical),
+            Metric::LastSeenMinutes => {
+                PerfData::minutes(name, elapsed, opts.warning, opts.critical)
+            }
+            Metric::StatusCode => {
+                let status = client.status_code(opts.repeater)?.ok_or_else(|| {
+                    Error::NotFound(format!("repeater {} has no status code", opts.repeater))
+                })?;
+                PerfData {
+                    minimum: None,
+                    ..PerfData::count(name, status)
+                }
+            }
+            Metric::LinkedStaticTalkgroups => {
+                let (count, _) = linked_talkgroup_counts()?;
+                PerfData::count(name, count as i64)
+            }
+            Metric::LinkedDynamicTalkgroups => {
+                let (_, count) = linked_talkgroup_counts()?;
+                PerfData::count(name, count as i64)
+            }
+            Metric::ApiResponseTime => PerfData {
+                unit: "ms".to_string(),
+                ..PerfData::count(name, api_rtt)
+            },
+        });
+    }
+    if let Some(dir) = &opts.state_dir {
+        if opts.metrics.contains(&Metric::ApiResponseTime) {
+            let (p50, p95) = latency_percentiles(opts, &StateDir::new(dir), api_rtt)?;
+            for (name, value) in [("api_rtt_p50_ms", p50), ("api_rtt_p95_ms", p95)] {
+                perfdata.push(PerfData {
+                    unit: "ms".to_string(),
+                    ..PerfData::count(name, value)
+                });
+            }
+        }
+    }
+
+    let mut notes = Vec::new();
+    if let (Some(call), Some(key)) = (&opts.aprs_call, &opts.aprs_apikey) {
+        let aprs_elapsed = aprs::last_heard_elapsed_with(client.transport(), call, key)?;
+        notes.push(aprs_description(
+            elapsed < opts.warning,
+            aprs_elapsed < opts.warning,
+        ));
+        perfdata.push(PerfData::duration(
+            "aprs_last_heard",
+            aprs_elapsed,
+            opts.warning,
+            opts.critical,
+        ));
+    }
+    if let Some(pattern) = &opts.mmdvm_log {
+        let local_elapsed = mmdvm::last_network_activity_elapsed(pattern)?;
+        notes.push(mmdvm_description(
+            elapsed < opts.warning,
+            local_elapsed < opts.warning,
+        ));
+        perfdata.push(PerfData::duration(
+            "mmdvm_last_activity",
+            local_elapsed,
+            opts.warning,
+            opts.critical,
+        ));
+    }
+    if opts.static_tg_count_warn.is_some() || opts.static_tg_count_crit.is_some() {
+        let count = client.static_talkgroup_count(opts.repeater)?;
+        perfdata.push(PerfData::minimum_count(
+            "static_tg_count",
+            count as i64,
+            opts.static_tg_count_warn,
+            opts.static_tg_count_crit,
+        ));
+    }
+    let mut duplicates = Vec::new();
+    if opts.check_duplicates {
+        duplicates = duplicate_ids(client, opts)?;
+        perfdata.push(PerfData {
+            warning: Some(1.0),
+            ..PerfData::count("duplicate_ids", duplicates.len() as i64)
+        });
+    }
+    let mut unexpected = Vec::new();
+    if !opts.expected_fields.is_empty() {
+        unexpected = unexpected_fields(client, opts)?;
+        perfdata.push(PerfData {
+            warning: Some(1.0),
+            ..PerfData::count("unexpected_fields", unexpected.len() as i64)
+        });
+    }
+    if let Some(max_drift) = opts.max_position_drift {
+        let drift = position_drift(client, opts)?;
+        perfdata.push(PerfData::meters("position_drift", drift, max_drift));
+    }
+    if let (Some(max_reconnects), Some(dir)) = (opts.max_reconnects, &opts.state_dir) {
+        let master = client.last_known_master(opts.repeater)?;
+        let fresh = elapsed < opts.warning;
+        let reconnects = count_reconnects(opts, &StateDir::new(dir), master, fresh)?;
+        perfdata.push(PerfData {
+            warning: Some(f64::from(max_reconnects) + 1.0),
+            ..PerfData::count("reconnects", reconnects)
+        });
+    }
+    let description = if notes.is_empty() {
+        "online status".to_string()
+    } else {
+        notes.join("; ")
+    };
+
+    let mut outcome = CheckOutcome::new(name, description, perfdata);
+    outcome.last_seen = Some(last_seen);
+    outcome.elapsed = Some(elapsed);
+    if !duplicates.is_empty() {
+        let ids: Vec<String> = duplicates.iter().map(u32::to_string).collect();
+        outcome.messages.push(format!(
+            "callsign also claimed by active device(s) {}",
+            ids.join(", ")
+        ));
+    }
+    outcome.messages.extend(unexpected);
+    if let Some(dir) = &opts.state_dir {
+        if opts.samples > 1 {
+            apply_samples(&mut outcome, opts, &StateDir::new(dir))?;
+        }
+    }
+    Ok(outcome)
+}
+
+/// Ids of the other devices registered with the repeater's callsign and seen more recently
+/// than the critical threshold.
+fn duplicate_ids(client: &BmClient, opts: &CheckOptions) -> Result<Vec<u32>> {
+    let metadata = client.metadata(opts.repeater)?;
+    let callsign = metadata
+        .callsign
+        .as_deref()
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .ok_or_else(|| Error::NotFound(format!("repeater {} has no callsign", opts.repeater)))?;
+    let now = Utc::now();
+    Ok(client
+        .devices_by_callsign(callsign)?
+        .into_iter()
+        .filter(|(id, last_seen)| {
+            *id != opts.repeater && now.signed_duration_since(*last_seen) < opts.critical
+        })
+        .map(|(id, _)| id)
+        .collect())
+}
+
+/// Describe each field of the repeater record that differs from its expected value.
+///
+/// Strings are compared as is and other values in their JSON form, a missing or null field
+/// matching only an empty value.
+fn unexpected_fields(client: &BmClient, opts: &CheckOptions) -> Result<Vec<String>> {
+    let record = client.device_record(opts.repeater)?;
+    Ok(opts
+        .expected_fields
+        .iter()
+        .filter_map(|(name, expected)| {
+            let actual = match record.get(name) {
+                None | Some(serde_json::Value::Null) => None,
+                Some(serde_json::Value::String(value)) => Some(value.clone()),
+                Some(value) => Some(value.to_string()),
+            };
+            match actual {
+                Some(actual) if actual == *expected => None,
+                None if expected.is_empty() => None,
+                Some(actual) => Some(format!(
+                    "field {} is {:?}, expected {:?}",
+                    name, actual, expected
+                )),
+                None => Some(format!(
+                    "field {} is missing, expected {:?}",
+                    name, expected
+                )),
+            }
+        })
+        .collect())
+}
+
+/// Number of API response times kept per repeater for the latency percentiles.
+const LATENCY_SAMPLES: usize = 100;
+
+/// Record the API response time `api_rtt` in the latency history of the repeater and return
+/// the median and 95th percentile of the history, in milliseconds.
+fn latency_percentiles(
+    opts: &CheckOptions,
+    state_dir: &StateDir,
+    api_rtt: i64,
+) -> Result<(i64, i64)> {
+    let key = format!("latency-{}", opts.repeater);
+    let mut history = state_dir.update(&key, |history: &mut Vec<i64>| {
+        history.push(api_rtt);
+        let excess = history.len().saturating_sub(LATENCY_SAMPLES);
+        history.drain(..excess);
+        history.clone()
+    })?;
+    history.sort_unstable();
+    // Nearest rank, so that a percentile is always one of the recorded times.
+    let rank = |percent: usize| history[(history.len() * percent).div_ceil(100).max(1) - 1];
+    Ok((rank(50), rank(95)))
+}
+
+/// Window over which reconnects are counted, in seconds.
+const RECONNECT_WINDOW: Duration = Duration::hours(1);
+
+/// Connection indicators of a repeater remembered between checks.
+#[derive(Default, Serialize, Deserialize)]
+struct ReconnectState {
+    /// Master server the repeater was connected to at the last check
+    master: Option<u32>,
+    /// Whether the repeater was seen stale at the last check
+    stale: bool,
+    /// Times of the reconnects within the window
+    reconnects: Vec<i64>,
+}
+
+/// Record a reconnect when the repeater moved to another master or came back after being
+/// seen stale since the last check, and return the number of reconnects in the last hour.
+///
+/// Polling only sees the outages spanning a check, so the count is a lower bound.
+fn count_reconnects(
+    opts: &CheckOptions,
+    state_dir: &StateDir,
+    master: Option<u32>,
+    fresh: bool,
+) -> Result<i64> {
+    let key = format!("reconnects-{}", opts.repeater);
+    let now = Utc::now().timestamp();
+    state_dir.update(&key, |state: &mut ReconnectState| {
+        let moved = state.master.is_some() && master.is_some() && state.master != master;
+        if moved || (state.stale && fresh) {
+            state.reconnects.push(now);
+        }
+        state
+            .reconnects
+            .retain(|at| now - at < RECONNECT_WINDOW.num_seconds());
+        state.master = master.or(state.master);
+        state.stale = !fresh;
+        state.reconnects.len() as i64
+    })
+}
+
+/// Repeater metadata cached in the state directory with the time it was fetched.
+#[derive(Default, Serialize, Deserialize)]
+struct MetadataState {
+    metadata: Option<RepeaterMetadata>,
+    fetched_at: i64,
+}
+
+/// Refresh cached metadata once a day, as it only changes when edited in SelfCare.
+const METADATA_MAX_AGE: i64 = 24 * 3600;
+
+/// Return the repeater metadata cached in the state directory, fetching it when missing or
+/// outdated.
+///
+/// Metadata only makes the status line more readable, so failing to fetch or cache it never
+/// fails the check: the cached copy, if any, is used instead.
+fn repeater_metadata(opts: &CheckOptions, client: Option<&BmClient>) -> Option<RepeaterMetadata> {
+    let state_dir = StateDir::new(opts.state_dir.as_ref()?);
+    let key = format!("metadata-{}", opts.repeater);
+    let update = |state: &mut MetadataState| {
+        let now = chrono::Utc::now().timestamp();
+        if state.metadata.is_none() || now - state.fetched_at >= METADATA_MAX_AGE {
+            let metadata = client.map(|c| opts.provider(c).metadata(opts.repeater));
+            if let Some(Ok(metadata)) = metadata {
+                state.metadata = Some(metadata);
+                state.fetched_at = now;
+            }
+        }
+        state.metadata.clone()
+    };
+    state_dir
+        .update(&key, update)
+        .ok()
+        .flatten()
+        .or_else(|| state_dir.load::<MetadataState>(&key).ok()?.metadata)
+}
+
+/// Return the distance in meters between the repeater position and the expected one, or the
+/// first one recorded in the state directory.
+///
+/// Remove the `position-<repeater>` state file to accept a new position after a genuine move.
+fn position_drift(client: &BmClient, opts: &CheckOptions) -> Result<i64> {
+    let position = client.position(opts.repeater)?.ok_or_else(|| {
+        Error::NotFound(format!(
+            "repeater {} has no position registered",
+            opts.repeater
+        ))
+    })?;
+    let reference = match (opts.expected_position, &opts.state_dir) {
+        (Some(expected), _) => expected,
+        (None, Some(dir)) => {
+            let key = format!("position-{}", opts.repeater);
+            StateDir::new(dir).update(&key, |recorded: &mut Option<(f64, f64)>| {
+                *recorded.get_or_insert(position)
+            })?
+        }
+        (None, None) => position,
+    };
+    Ok(distance_meters(reference, position).round() as i64)
+}
+
+/// Great-circle distance between two latitude and longitude pairs in degrees.
+fn distance_meters((lat1, lon1): (f64, f64), (lat2, lon2): (f64, f64)) -> f64 {
+    const EARTH_RADIUS: f64 = 6_371_000.0;
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let d_phi = (lat2 - lat1).to_radians();
+    let d_lambda = (lon2 - lon1).to_radians();
+    let a = (d_phi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (d_lambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().asin()
+}
+
+/// Describe the site status from the freshness of the BM and APRS views.
+fn aprs_description(bm_fresh: bool, aprs_fresh: bool) -> &'static str {
+    match (bm_fresh, aprs_fresh) {
+        (true, true) => "online status, APRS beacon heard",
+        (true, false) => "online status, APRS beacon not heard recently",
+        (false, true) => "BM link down, site still heard on APRS",
+        (false, false) => "site offline, neither BM nor APRS activity",
+    }
+}
+
+/// Describe which of the BM API and local MMDVMHost views is stale.
+fn mmdvm_description(api_fresh: bool, local_fresh: bool) -> &'static str {
+    match (api_fresh, local_fresh) {
+        (true, true) => "online status, local MMDVMHost network activity",
+        (true, false) => "online status, local MMDVMHost log is stale",
+        (false, true) => "BM API view is stale, local MMDVMHost reports network activity",
+        (false, false) => "BM API and local MMDVMHost log are both stale",
+    }
+}
+
+/// Last reported state of a repeater and when it was entered.
+#[derive(Default, Serialize, Deserialize)]
+struct TransitionState {
+    state: Option<NagiosState>,
+    since: i64,
+}
+
+/// Append the previous state and how long it lasted to the description when the state
+/// changed since the last check, e.g. `was CRITICAL for 42m, recovered at 14:03Z`, or the
+/// outage duration when CRITICAL for longer than the escalation time.
+fn note_transition(
+    outcome: &mut CheckOutcome,
+    opts: &CheckOptions,
+    state_dir: &StateDir,
+) -> Result<()> {
+    let key = format!("transition-{}", opts.repeater);
+    let now = chrono::Utc::now();
+    state_dir.update(&key, |previous: &mut TransitionState| {
+        if previous.state == Some(outcome.state) {
+            let elapsed = Duration::seconds(now.timestamp() - previous.since);
+            if outcome.state == NagiosState::Critical
+                && opts.escalate_after.is_some_and(|after| elapsed >= after)
+            {
+                outcome.escalated = true;
+                outcome.description = format!(
+                    "{} (long-term outage, CRITICAL for {})",
+                    outcome.description,
+                    duration::format_duration(elapsed)
+                );
+            }
+            return;
+        }
+        if let Some(state) = previous.state {
+            let at = now.format("%H:%MZ");
+            let change = if outcome.state == NagiosState::Ok {
+                format!("recovered at {}", at)
+            } else {
+                format!("{} since {}", outcome.state, at)
+            };
+            outcome.description = format!(
+                "{} (was {} for {}, {})",
+                outcome.description,
+                state,
+                duration::format(now.timestamp() - previous.since),
+                change
+            );
+        }
+        previous.state = Some(outcome.state);
+        previous.since = now.timestamp();
+    })
+}
+
+/// Threshold breaches remembered between checks of a repeater when using samples.
+#[derive(Default, Serialize, Deserialize)]
+struct SamplesState {
+    /// Last state confirmed by enough consecutive samples
+    hard_state: Option<NagiosState>,
+    /// Number of consecutive checks breaching a threshold
+    breaches: u32,
+}
+
+/// Keep reporting the last confirmed state until a breach was seen in `opts.samples`
+/// consecutive checks, like nagios max_check_attempts.
+fn apply_samples(
+    outcome: &mut CheckOutcome,
+    opts: &CheckOptions,
+    state_dir: &StateDir,
+) -> Result<()> {
+    let key = format!("check-{}", opts.repeater);
+    state_dir.update(&key, |samples: &mut SamplesState| {
+        let state = outcome.state;
+        if state == NagiosState::Ok {
+            samples.breaches = 0;
+            samples.hard_state = None;
+        } else {
+            samples.breaches += 1;
+            if samples.breaches >= opts.samples {
+                samples.hard_state = Some(state);
+            } else {
+                outcome.state = samples.hard_state.unwrap_or(NagiosState::Ok);
+                outcome.description = format!(
+                    "{} (soft {}, sample {} of {})",
+                    outcome.description, state, samples.breaches, opts.samples
+                );
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::test_dir;
+    use crate::transport::stub::StubTransport;
+
+    const REPEATER: u32 = 270107;
+
+    /// Options checking `REPEATER` with a state directory of its own.
+    fn builder(name: &str) -> (CheckOptionsBuilder, StateDir) {
+        let dir = test_dir(name);
+        let builder = CheckOptions::builder()
+            .repeater(REPEATER)
+            .api_url("http://stub")
+            .state_dir(&dir);
+        (builder, StateDir::new(dir))
+    }
+
+    fn outcome(state: NagiosState) -> CheckOutcome {
+        let mut outcome = CheckOutcome::new("test".to_string(), "last seen".to_string(), vec![]);
+        outcome.state = state;
+        outcome
+    }
+
+    /// Run `states` through `apply_samples` and return the reported states and descriptions.
+    fn sampled(opts: &CheckOptions, dir: &StateDir, states: &[NagiosState]) -> Vec<String> {
+        states
+            .iter()
+            .map(|&state| {
+                let mut outcome = outcome(state);
+                apply_samples(&mut outcome, opts, dir).unwrap();
+                format!("{}: {}", outcome.state, outcome.description)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn samples_confirm_consecutive_breaches() {
+        let (builder, dir) = builder("samples");
+        let opts = builder.samples(3).build().unwrap();
+        use NagiosState::*;
+        let reported = sampled(&opts, &dir, &[Warning, Warning, Critical, Critical, Ok]);
+        assert_eq!(
+            reported,
+            [
+                "OK: last seen (soft WARNING, sample 1 of 3)",
+                "OK: last seen (soft WARNING, sample 2 of 3)",
+                "CRITICAL: last seen",
+                "CRITICAL: last seen",
+                "OK: last seen",
+            ]
+        );
+    }
+
+    #[test]
+    fn samples_keep_the_hard_state_until_confirmed() {
+        let (builder, dir) = builder("samples_hard");
+        let opts = builder.samples(2).build().unwrap();
+        use NagiosState::*;
+        let reported = sampled(&opts, &dir, &[Critical, Critical, Ok, Warning, Warning]);
+        assert_eq!(
+            reported,
+            [
+                "OK: last seen (soft CRITICAL, sample 1 of 2)",
+                "CRITICAL: last seen",
+                "OK: last seen",
+                "OK: last seen (soft WARNING, sample 1 of 2)",
+                "WARNING: last seen",
+            ]
+        );
+    }
+
+    #[test]
+    fn single_sample_is_hard_at_once() {
+        let (builder, dir) = builder("samples_single");
+        let opts = builder.samples(1).build().unwrap();
+        let reported = sampled(&opts, &dir, &[NagiosState::Critical]);
+        assert_eq!(reported, ["CRITICAL: last seen"]);
+    }
+
+    #[test]
+    fn minutes_keep_the_precision_of_seconds() {
+        let p = PerfData::minutes(
+            "last_seen_min",
+            Duration::seconds(119),
+            Duration::seconds(90),
+            Duration::minutes(2),
+        );
+        assert_eq!(p.to_string(), "'last_seen_min'=2.0;1.5;2;0;");
+        assert_eq!(p.state(), NagiosState::Warning);
+        let p = PerfData { value: 2.0, ..p };
+        assert_eq!(p.state(), NagiosState::Critical);
+    }
+
+    #[test]
+    fn low_is_bad_thresholds_are_ranges() {
+        let p = PerfData::minimum_count("static_tg_count", 3, Some(5), Some(1));
+        assert_eq!(p.to_string(), "'static_tg_count'=3;@~:5;@~:1;0;");
+        assert_eq!(p.state(), NagiosState::Warning);
+        for (text, value) in [("@~:5", 3.0), ("@~:1", 1.0)] {
+            assert!(text.parse::<Range>().unwrap().alerts(value), "{}", text);
+            assert!(!text.parse::<Range>().unwrap().alerts(6.0), "{}", text);
+        }
+    }
+
+    /// Run an outcome of `state` through `note_transition` and return it.
+    fn noted(opts: &CheckOptions, dir: &StateDir, state: NagiosState) -> CheckOutcome {
+        let mut outcome = outcome(state);
+        note_transition(&mut outcome, opts, dir).unwrap();
+        outcome
+    }
+
+    /// Remember `state` as entered `ago` before now.
+    fn entered(dir: &StateDir, state: NagiosState, ago: Duration) {
+        let previous = TransitionState {
+            state: Some(state),
+            since: (Utc::now() - ago).timestamp(),
+        };
+        dir.save(&format!("transition-{}", REPEATER), &previous)
+            .unwrap();
+    }
+
+    /// Replace the `HH:MMZ` time of the transition, which depends on when the test runs.
+    fn without_time(description: &str) -> String {
+        let (head, time) = description.rsplit_once(' ').unwrap();
+        assert_eq!(time.len(), "HH:MMZ)".len(), "{}", description);
+        format!("{} HH:MMZ)", head)
+    }
+
+    #[test]
+    fn first_state_is_not_a_transition() {
+        let (builder, dir) = builder("transition_first");
+        let opts = builder.build().unwrap();
+        let outcome = noted(&opts, &dir, NagiosState::Critical);
+        assert_eq!(outcome.description, "last seen");
+    }
+
+    #[test]
+    fn transition_then_unchanged_state() {
+        let (builder, dir) = builder("transition");
+        let opts = builder.build().unwrap();
+        entered(&dir, NagiosState::Critical, Duration::minutes(42));
+
+        let outcome = noted(&opts, &dir, NagiosState::Ok);
+        assert_eq!(
+            without_time(&outcome.description),
+            "last seen (was CRITICAL for 42m, recovered at HH:MMZ)"
+        );
+        let outcome = noted(&opts, &dir, NagiosState::Ok);
+        assert_eq!(outcome.description, "last seen");
+
+        let outcome = noted(&opts, &dir, NagiosState::Warning);
+        assert_eq!(
+            without_time(&outcome.description),
+            "last seen (was OK for 0s, WARNING since HH:MMZ)"
+        );
+        assert!(!outcome.escalated);
+    }
+
+    #[test]
+    fn critical_escalates_after_the_escalation_time() {
+        let (builder, dir) = builder("escalation");
+        let opts = builder.escalate_after(Duration::hours(6)).build().unwrap();
+        entered(&dir, NagiosState::Critical, Duration::hours(6));
+        let outcome = noted(&opts, &dir, NagiosState::Critical);
+        assert!(outcome.escalated);
+        assert_eq!(
+            outcome.description,
+            "last seen (long-term outage, CRITICAL for 6h)"
+        );
+    }
+
+    #[test]
+    fn escalation_waits_for_the_escalation_time() {
+        let (builder, dir) = builder("escalation_wait");
+        let opts = builder.escalate_after(Duration::hours(6)).build().unwrap();
+        entered(&dir, NagiosState::Critical, Duration::minutes(359));
+        let outcome = noted(&opts, &dir, NagiosState::Critical);
+        assert!(!outcome.escalated);
+        assert_eq!(outcome.description, "last seen");
+
+        entered(&dir, NagiosState::Warning, Duration::hours(7));
+        let outcome = noted(&opts, &dir, NagiosState::Warning);
+        assert!(!outcome.escalated);
+    }
+
+    #[test]
+    fn latency_of_a_single_sample() {
+        let (builder, dir) = builder("latency_single");
+        let opts = builder.build().unwrap();
+        assert_eq!(latency_percentiles(&opts, &dir, 42).unwrap(), (42, 42));
+    }
+
+    #[test]
+    fn latency_percentiles_use_the_nearest_rank() {
+        let (options, dir) = builder("latency_rank");
+        let opts = options.build().unwrap();
+        let percentiles: Vec<_> = [30, 10, 20]
+            .into_iter()
+            .map(|rtt| latency_percentiles(&opts, &dir, rtt).unwrap())
+            .collect();
+        assert_eq!(percentiles, [(30, 30), (10, 30), (20, 30)]);
+
+        let (options, dir) = builder("latency_rank_ten");
+        let opts = options.build().unwrap();
+        let mut last = (0, 0);
+        for rtt in 1..=10 {
+            last = latency_percentiles(&opts, &dir, rtt).unwrap();
+        }
+        assert_eq!(last, (5, 10));
+    }
+
+    #[test]
+    fn latency_history_keeps_the_last_samples() {
+        let (builder, dir) = builder("latency_history");
+        let opts = builder.build().unwrap();
+        let mut last = (0, 0);
+        for rtt in 1..=150 {
+            last = latency_percentiles(&opts, &dir, rtt).unwrap();
+        }
+        assert_eq!(last, (100, 145));
+        let history: Vec<i64> = dir.load(&format!("latency-{}", REPEATER)).unwrap();
+        assert_eq!(history, (51..=150).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn reconnects_leave_the_window_after_an_hour() {
+        let (builder, dir) = builder("reconnects_window");
+        let opts = builder.build().unwrap();
+        let now = Utc::now().timestamp();
+        let window = RECONNECT_WINDOW.num_seconds();
+        let state = ReconnectState {
+            master: Some(2702),
+            stale: false,
+            reconnects: vec![now - window, now - window + 10],
+        };
+        dir.save(&format!("reconnects-{}", REPEATER), &state)
+            .unwrap();
+        assert_eq!(count_reconnects(&opts, &dir, Some(2702), true).unwrap(), 1);
+    }
+
+    #[test]
+    fn reconnects_count_master_moves_and_returns() {
+        let (builder, dir) = builder("reconnects");
+        let opts = builder.build().unwrap();
+        let counts: Vec<_> = [
+            (Some(2702), true),
+            (Some(2702), true),
+            (Some(2041), true),
+            (None, true),
+            (Some(2041), false),
+            (Some(2041), false),
+            (Some(2041), true),
+        ]
+        .into_iter()
+        .map(|(master, fresh)| count_reconnects(&opts, &dir, master, fresh).unwrap())
+        .collect();
+        assert_eq!(counts, [0, 0, 1, 1, 1, 1, 2]);
+    }
+
+    /// Device record of `REPEATER` with the extra `fields`, last seen now.
+    fn device(fields: &str) -> String {
+        let last_seen = Utc::now().format("%Y-%m-%d %H:%M:%S");
+        format!(
+            r#"{{"id": {}, "last_seen": "{}", {}}}"#,
+            REPEATER, last_seen, fields
+        )
+    }
+
+    /// Entry of the callsign search for `id`, last seen `ago` before now.
+    fn callsign_device(id: u32, ago: Duration) -> String {
+        let last_seen = (Utc::now() - ago).format("%Y-%m-%d %H:%M:%S");
+        format!(r#"{{"id": {}, "last_seen": "{}"}}"#, id, last_seen)
+    }
+
+    #[test]
+    fn duplicates_are_other_recent_devices() {
+        let devices = [
+            callsign_device(REPEATER, Duration::zero()),
+            callsign_device(270108, Duration::minutes(29)),
+            callsign_device(270109, Duration::minutes(30)),
+        ];
+        let stub = Arc::new(
+            StubTransport::new()
+                .with("device/270107", 200, &device(r#""callsign": "ON0ABC""#))
+                .with(
+                    "device/byCall?callsign=ON0ABC",
+                    200,
+                    &format!("[{}]", devices.join(",")),
+                ),
+        );
+        let (builder, _) = builder("duplicates");
+        let opts = builder
+            .transport(stub.clone())
+            .crit(Duration::minutes(30))
+            .build()
+            .unwrap();
+        let client = opts.client().unwrap();
+        assert_eq!(duplicate_ids(&client, &opts).unwrap(), [270108]);
+        assert_eq!(
+            stub.urls(),
+            [
+                "http://stub/device/270107",
+                "http://stub/device/byCall?callsign=ON0ABC"
+            ]
+        );
+    }
+
+    #[test]
+    fn no_duplicates_in_a_single_match() {
+        let stub = StubTransport::new()
+            .with("device/270107", 200, &device(r#""callsign": "ON0ABC""#))
+            .with(
+                "device/byCall?callsign=ON0ABC",
+                200,
+                &callsign_device(REPEATER, Duration::zero()),
+            );
+        let (builder, _) = builder("duplicates_single");
+        let opts = builder.transport(Arc::new(stub)).build().unwrap();
+        let client = opts.client().unwrap();
+        assert!(duplicate_ids(&client, &opts).unwrap().is_empty());
+    }
+
+    #[test]
+    fn duplicates_need_a_callsign() {
+        let stub = StubTransport::new().with("device/270107", 200, &device(r#""callsign": " ""#));
+        let (builder, _) = builder("duplicates_callsign");
+        let opts = builder.transport(Arc::new(stub)).build().unwrap();
+        let client = opts.client().unwrap();
+        let e = duplicate_ids(&client, &opts).unwrap_err();
+        assert_eq!(e.to_string(), "repeater 270107 has no callsign");
+    }
+
+    #[test]
+    fn unexpected_fields_of_the_record() {
+        let record = device(
+            r#""hardware": "MMDVM_HS_Hat", "colorcode": 1, "firmware": null, "tx": "439.5000""#,
+        );
+        let stub = StubTransport::new().with("device/270107", 200, &record);
+        let (builder, _) = builder("fields");
+        let opts = builder
+            .transport(Arc::new(stub))
+            .expect_field("hardware", "MMDVM_HS_Hat")
+            .expect_field("colorcode", "1")
+            .expect_field("firmware", "")
+            .expect_field("pep", "")
+            .expect_field("tx", "439.0000")
+            .expect_field("rx", "431.8375")
+            .build()
+            .unwrap();
+        let client = opts.client().unwrap();
+        assert_eq!(
+            unexpected_fields(&client, &opts).unwrap(),
+            [
+                r#"field tx is "439.5000", expected "439.0000""#,
+                r#"field rx is missing, expected "431.8375""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn no_unexpected_fields_without_expectations() {
+        let stub = StubTransport::new().with("device/270107", 200, &device(r#""tx": "439""#));
+        let (builder, _) = builder("fields_none");
+        let opts = builder.transport(Arc::new(stub)).build().unwrap();
+        let client = opts.client().unwrap();
+        assert!(unexpected_fields(&client, &opts).unwrap().is_empty());
+    }
+
+    #[test]
+    fn distance_of_a_degree_of_latitude() {
+        assert_eq!(distance_meters((50.63, 5.57), (50.63, 5.57)), 0.0);
+        let degree = distance_meters((50.0, 5.0), (51.0, 5.0));
+        assert_eq!(degree.round(), 111_195.0);
+        assert_eq!(distance_meters((51.0, 5.0), (50.0, 5.0)), degree);
+        // A degree of longitude shrinks with the cosine of the latitude.
+        let longitude = distance_meters((60.0, 5.0), (60.0, 6.0));
+        assert!((longitude - degree / 2.0).abs() < 1.0, "{}", longitude);
+    }
+
+    #[test]
+    fn position_drift_from_the_first_position() {
+        let stub = StubTransport::new()
+            .with("device/270107", 200, &device(r#""lat": 50.0, "lng": 5.0"#))
+            .with("device/270107", 200, &device(r#""lat": 50.01, "lng": 5.0"#));
+        let (builder, _) = builder("drift_recorded");
+        let opts = builder.transport(Arc::new(stub)).build().unwrap();
+        // One client per check, as the record is fetched once per client.
+        assert_eq!(position_drift(&opts.client().unwrap(), &opts).unwrap(), 0);
+        assert_eq!(
+            position_drift(&opts.client().unwrap(), &opts).unwrap(),
+            1112
+        );
+    }
+
+    #[test]
+    fn position_drift_from_the_expected_position() {
+        let stub =
+            StubTransport::new().with("device/270107", 200, &device(r#""lat": 50.01, "lng": 5.0"#));
+        let (builder, _) = builder("drift_expected");
+        let opts = builder
+            .transport(Arc::new(stub))
+            .expected_position(50.0, 5.0)
+            .max_position_drift(500)
+            .build()
+            .unwrap();
+        let client = opts.client().unwrap();
+        assert_eq!(position_drift(&client, &opts).unwrap(), 1112);
+    }
+
+    #[test]
+    fn position_drift_needs_a_position() {
+        let stub = StubTransport::new().with("device/270107", 200, &device(r#""lat": null"#));
+        let (builder, _) = builder("drift_missing");
+        let opts = builder.transport(Arc::new(stub)).build().unwrap();
+        let client = opts.client().unwrap();
+        let e = position_drift(&client, &opts).unwrap_err();
+        assert_eq!(e.to_string(), "repeater 270107 has no position registered");
+    }
+
+    #[test]
+    fn check_fetches_the_device_record_once() {
+        let record = device(
+            r#""callsign": "ON0ABC", "city": "Liège", "status": 3, "lastKnownMaster": 2702,
+               "lat": 50.63, "lng": 5.57, "hardware": "MMDVM_HS_Hat""#,
+        );
+        let stub = Arc::new(
+            StubTransport::new()
+                .with("device/270107", 200, &record)
+                .with(
+                    "device/byCall?callsign=ON0ABC",
+                    200,
+                    &callsign_device(REPEATER, Duration::zero()),
+                ),
+        );
+        let (builder, _) = builder("device_once");
+        let opts = builder
+            .transport(stub.clone())
+            .metrics([Metric::LastSeen, Metric::StatusCode])
+            .check_duplicates(true)
+            .expect_field("hardware", "MMDVM_HS_Hat")
+            .max_reconnects(3)
+            .max_position_drift(500)
+            .build()
+            .unwrap();
+        let outcome = check(&opts);
+        assert_eq!(outcome.state, NagiosState::Ok, "{}", outcome.description);
+        assert_eq!(outcome.name, "BrandMeister repeater 270107 ON0ABC (Liège)");
+        let urls = stub.urls();
+        let device_urls = urls.iter().filter(|u| u.ends_with("/device/270107"));
+        assert_eq!(device_urls.count(), 1, "{:?}", urls);
+    }
+
+    #[test]
+    fn linked_talkgroups_fetch_the_profile_once() {
+        let profile = r#"{"staticSubscriptions": [{"talkgroup": 206}, {"talkgroup": 2061}],
+                          "dynamicSubscriptions": [{"talkgroup": 91}]}"#;
+        let stub = Arc::new(
+            StubTransport::new()
+                .with("device/270107", 200, &device(r#""status": 3"#))
+                .with("device/270107/profile", 200, profile),
+        );
+        let (builder, _) = builder("linked_once");
+        let opts = builder
+            .transport(stub.clone())
+            .metrics([
+                Metric::LastSeen,
+                Metric::LinkedStaticTalkgroups,
+                Metric::LinkedDynamicTalkgroups,
+            ])
+            .build()
+            .unwrap();
+        let outcome = check(&opts);
+        let values: Vec<_> = outcome.perfdata.iter().map(|p| p.value).collect();
+        assert_eq!(values[1..], [2.0, 1.0], "{:?}", outcome.perfdata);
+        let urls = stub.urls();
+        let profiles = urls.iter().filter(|u| u.ends_with("/profile"));
+        assert_eq!(profiles.count(), 1, "{:?}", urls);
+    }
+
+    #[test]
+    fn api_rtt_times_the_fetch_made_for_the_metadata() {
+        let record = device(r#""callsign": "ON0ABC", "city": "Liège""#);
+        let stub = StubTransport::new()
+            .with("device/270107", 200, &record)
+            .with_delay(std::time::Duration::from_millis(50));
+        let (builder, dir) = builder("rtt_metadata");
+        let opts = builder
+            .transport(Arc::new(stub))
+            .metrics([Metric::LastSeen, Metric::ApiResponseTime])
+            .build()
+            .unwrap();
+        let outcome = check(&opts);
+        assert_eq!(outcome.name, "BrandMeister repeater 270107 ON0ABC (Liège)");
+        let rtt = outcome
+            .perfdata
+            .iter()
+            .find(|p| p.name == "api_rtt_ms")
+            .unwrap();
+        assert!(rtt.value >= 50.0, "{:?}", outcome.perfdata);
+        assert_eq!(rtt.unit, "ms");
+        let history: Vec<i64> = dir.load("latency-270107").unwrap();
+        assert!(history.iter().all(|&ms| ms >= 50), "{:?}", history);
+        let units: Vec<_> = outcome.perfdata.iter().map(|p| p.unit.as_str()).collect();
+        assert_eq!(units, ["s", "ms", "ms", "ms"]);
+    }
+}
@@ -0,0 +1,158 @@
+//! Check of the BrandMeister API itself, so that repeater UNKNOWNs can be correlated with an
+//! API outage through a nagios service dependency.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use chrono::Duration;
+
+use super::{invalid, CheckOutcome, NagiosState, PerfData};
+use crate::client::API_URL;
+use crate::output::sanitize_label;
+use crate::transport::default_transport;
+use crate::{Result, Transport};
+
+/// Configuration of an API check, created with [ApiCheckOptions::builder].
+///
+/// Example:
+/// ```no_run
+/// use brandmeister::check::{check_api, ApiCheckOptions};
+/// let opts = ApiCheckOptions::builder().build().unwrap();
+/// let outcome = check_api(&opts);
+/// println!("{}: {}", outcome.state, outcome.description);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ApiCheckOptions {
+    urls: Vec<String>,
+    warning: Duration,
+    critical: Duration,
+    transport: Option<Arc<dyn Transport>>,
+}
+
+impl ApiCheckOptions {
+    /// Start configuring an API check of the public BrandMeister API.
+    pub fn builder() -> ApiCheckOptionsBuilder {
+        ApiCheckOptionsBuilder {
+            options: ApiCheckOptions {
+                urls: Vec::new(),
+                warning: Duration::seconds(1),
+                critical: Duration::seconds(5),
+                transport: None,
+            },
+        }
+    }
+}
+
+/// Builder of [ApiCheckOptions], validating the configuration in [ApiCheckOptionsBuilder::build].
+#[derive(Debug, Clone)]
+pub struct ApiCheckOptionsBuilder {
+    options: ApiCheckOptions,
+}
+
+impl ApiCheckOptionsBuilder {
+    /// Probe `url`, e.g. an API mirror. Defaults to the public API when no URL is given.
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.options.urls.push(url.into());
+        self
+    }
+
+    /// Response time before warning state, 1 second by default.
+    pub fn warn(mut self, warning: Duration) -> Self {
+        self.options.warning = warning;
+        self
+    }
+
+    /// Response time before critical state, 5 seconds by default.
+    pub fn crit(mut self, critical: Duration) -> Self {
+        self.options.critical = critical;
+        self
+    }
+
+    /// Perform the HTTP requests with `transport` instead of the default one from the
+    /// `ureq` feature.
+    pub fn transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.options.transport = Some(transport);
+        self
+    }
+
+    /// Validate the configuration.
+    pub fn build(self) -> Result<ApiCheckOptions> {
+        let mut opts = self.options;
+        if opts.urls.is_empty() {
+            opts.urls.push(format!("{}/", API_URL));
+        }
+        if opts.warning < Duration::zero() || opts.critical < Duration::zero() {
+            return Err(invalid("thresholds must not be negative"));
+        }
+        if opts.warning > opts.critical {
+            return Err(invalid(format!(
+                "warning threshold {}ms is greater than critical threshold {}ms",
+                opts.warning.num_milliseconds(),
+                opts.critical.num_milliseconds()
+            )));
+        }
+        Ok(opts)
+    }
+}
+
+/// Label of the response time metric of `url`, from its host when probing several URLs.
+fn rtt_label(url: &str, several: bool) -> String {
+    if !several {
+        return "api_rtt".to_string();
+    }
+    let host = url
+        .split("://")
+        .nth(1)
+        .unwrap_or(url)
+        .split('/')
+        .next()
+        .unwrap_or(url);
+    sanitize_label(&format!("{}_rtt", host))
+}
+
+/// Check that the API answers at every configured URL, and how fast.
+///
+/// An endpoint is reachable when it answers with a status below 500. The check is CRITICAL
+/// when no endpoint is reachable and WARNING when only some are.
+pub fn check_api(opts: &ApiCheckOptions) -> CheckOutcome {
+    let name = "BrandMeister API".to_string();
+    let transport = match &opts.transport {
+        Some(transport) => transport.clone(),
+        None => match default_transport() {
+            Ok(transport) => transport,
+            Err(e) => return CheckOutcome::failed(name, &e),
+        },
+    };
+
+    let several = opts.urls.len() > 1;
+    let mut perfdata = Vec::new();
+    let mut failures = Vec::new();
+    for url in &opts.urls {
+        let start = Instant::now();
+        let result = transport.get(url, &[]);
+        let elapsed = start.elapsed().as_millis() as i64;
+        match result {
+            Ok(response) if response.status < 500 => perfdata.push(PerfData::milliseconds(
+                &rtt_label(url, several),
+                elapsed,
+                opts.warning.num_milliseconds(),
+                opts.critical.num_milliseconds(),
+            )),
+            Ok(response) => failures.push(format!("{} answered {}", url, response.status)),
+            Err(e) => failures.push(e.to_string()),
+        }
+    }
+
+    let reachable = opts.urls.len() - failures.len();
+    let mut description = format!("{} of {} endpoints reachable", reachable, opts.urls.len());
+    if !failures.is_empty() {
+        description = format!("{}: {}", description, failures.join("; "));
+    }
+    let mut outcome = CheckOutcome::new(name, description, perfdata);
+    if reachable == 0 {
+        outcome.state = NagiosState::Critical;
+    } else if !failures.is_empty() {
+        outcome.state = outcome.state.worst(NagiosState::Warning);
+    }
+    outcome
+}
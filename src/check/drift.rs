@@ -0,0 +1,132 @@
+//! Check of the changes of the repeater records since a baseline snapshot, a drift detector
+//! for any registration field.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use chrono::SecondsFormat;
+
+use super::{invalid, CheckOutcome, PerfData};
+use crate::client::API_URL;
+use crate::snapshot::Snapshot;
+use crate::{BmClient, Result, Transport};
+
+/// Configuration of a drift check, created with [DriftCheckOptions::builder].
+///
+/// Example:
+/// ```no_run
+/// use brandmeister::check::{check_drift, DriftCheckOptions};
+/// let opts = DriftCheckOptions::builder()
+///     .baseline("/var/lib/check_brandmeister/baseline.json")
+///     .build()
+///     .unwrap();
+/// println!("{}", check_drift(&opts).description);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DriftCheckOptions {
+    baseline: PathBuf,
+    snapshot: Option<PathBuf>,
+    api_url: String,
+    transport: Option<Arc<dyn Transport>>,
+}
+
+impl DriftCheckOptions {
+    /// Start configuring a comparison with a baseline snapshot.
+    pub fn builder() -> DriftCheckOptionsBuilder {
+        DriftCheckOptionsBuilder {
+            baseline: None,
+            options: DriftCheckOptions {
+                baseline: PathBuf::new(),
+                snapshot: None,
+                api_url: API_URL.to_string(),
+                transport: None,
+            },
+        }
+    }
+}
+
+/// Builder of [DriftCheckOptions], validating the configuration in
+/// [DriftCheckOptionsBuilder::build].
+#[derive(Debug, Clone)]
+pub struct DriftCheckOptionsBuilder {
+    baseline: Option<PathBuf>,
+    options: DriftCheckOptions,
+}
+
+impl DriftCheckOptionsBuilder {
+    /// Snapshot file to compare with, required.
+    pub fn baseline(mut self, path: impl Into<PathBuf>) -> Self {
+        self.baseline = Some(path.into());
+        self
+    }
+
+    /// Compare the later snapshot file at `path` instead of the current API records.
+    pub fn snapshot(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.snapshot = Some(path.into());
+        self
+    }
+
+    /// Use the BrandMeister API at `url` instead of the public one, e.g. a local proxy.
+    pub fn api_url(mut self, url: impl Into<String>) -> Self {
+        self.options.api_url = url.into();
+        self
+    }
+
+    /// Perform the HTTP requests with `transport` instead of the default one from the
+    /// `ureq` feature.
+    pub fn transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.options.transport = Some(transport);
+        self
+    }
+
+    /// Validate the configuration.
+    pub fn build(self) -> Result<DriftCheckOptions> {
+        let mut opts = self.options;
+        opts.baseline = self
+            .baseline
+            .ok_or_else(|| invalid("baseline snapshot is required"))?;
+        Ok(opts)
+    }
+}
+
+/// Compare the repeater records with the baseline snapshot, WARNING when any field changed.
+/// Failures are reported as an UNKNOWN outcome.
+pub fn check_drift(opts: &DriftCheckOptions) -> CheckOutcome {
+    let name = "BrandMeister repeater records".to_string();
+    match run(opts, name.clone()) {
+        Ok(outcome) => outcome,
+        Err(e) => CheckOutcome::failed(name, &e),
+    }
+}
+
+fn run(opts: &DriftCheckOptions, name: String) -> Result<CheckOutcome> {
+    let baseline = Snapshot::load(&opts.baseline)?;
+    let current = match &opts.snapshot {
+        Some(path) => Snapshot::load(path)?,
+        None => {
+            let client = match &opts.transport {
+                Some(transport) => BmClient::with_transport(transport.clone()),
+                None => BmClient::with_default_transport()?,
+            }
+            .with_api_url(&opts.api_url);
+            Snapshot::take(&client, &baseline.repeaters())?
+        }
+    };
+    let changes = baseline.diff(&current);
+    let since = baseline.taken_at.to_rfc3339_opts(SecondsFormat::Secs, true);
+    let description = match changes.len() {
+        0 => format!("no changes since {}", since),
+        n => format!("{} changed fields since {}", n, since),
+    };
+    let mut outcome = CheckOutcome::new(
+        name,
+        description,
+        vec![PerfData {
+            warning: Some(1.0),
+            ..PerfData::count("changed_fields", changes.len() as i64)
+        }],
+    );
+    // The changes tell what to look at, better than the threshold message.
+    outcome.messages = changes.iter().map(ToString::to_string).collect();
+    Ok(outcome)
+}
@@ -0,0 +1,174 @@
+//! Aggregate check of the repeaters connected to a BrandMeister master server, giving network
+//! coordinators a single canary service per master.
+
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+
+use super::{invalid, CheckOutcome, PerfData};
+use crate::client::API_URL;
+use crate::{BmClient, Error, Result, Transport};
+
+/// Configuration of a master fleet check, created with [FleetCheckOptions::builder].
+///
+/// Example:
+/// ```no_run
+/// use brandmeister::check::{check_master_fleet, FleetCheckOptions};
+/// let opts = FleetCheckOptions::builder().master(2701).build().unwrap();
+/// let outcome = check_master_fleet(&opts);
+/// println!("{}: {}", outcome.state, outcome.description);
+/// ```
+#[derive(Debug, Clone)]
+pub struct FleetCheckOptions {
+    master: u32,
+    stale_after: Duration,
+    ignore_after: Duration,
+    warning: i64,
+    critical: i64,
+    api_url: String,
+    transport: Option<Arc<dyn Transport>>,
+}
+
+impl FleetCheckOptions {
+    /// Start configuring a check of the repeaters of a master server.
+    pub fn builder() -> FleetCheckOptionsBuilder {
+        FleetCheckOptionsBuilder {
+            master: None,
+            options: FleetCheckOptions {
+                master: 0,
+                stale_after: Duration::minutes(15),
+                ignore_after: Duration::days(7),
+                warning: 10,
+                critical: 25,
+                api_url: API_URL.to_string(),
+                transport: None,
+            },
+        }
+    }
+}
+
+/// Builder of [FleetCheckOptions], validating the configuration in
+/// [FleetCheckOptionsBuilder::build].
+#[derive(Debug, Clone)]
+pub struct FleetCheckOptionsBuilder {
+    master: Option<u32>,
+    options: FleetCheckOptions,
+}
+
+impl FleetCheckOptionsBuilder {
+    /// ID of the BrandMeister master server, e.g. 2701, required.
+    pub fn master(mut self, master: u32) -> Self {
+        self.master = Some(master);
+        self
+    }
+
+    /// Inactive time after which a repeater counts as stale, 15 minutes by default.
+    pub fn stale_after(mut self, stale_after: Duration) -> Self {
+        self.options.stale_after = stale_after;
+        self
+    }
+
+    /// Inactive time after which a repeater is considered decommissioned and left out of the
+    /// statistics, 7 days by default.
+    pub fn ignore_after(mut self, ignore_after: Duration) -> Self {
+        self.options.ignore_after = ignore_after;
+        self
+    }
+
+    /// Percentage of stale repeaters before warning state, 10 by default.
+    pub fn warn(mut self, percent: i64) -> Self {
+        self.options.warning = percent;
+        self
+    }
+
+    /// Percentage of stale repeaters before critical state, 25 by default.
+    pub fn crit(mut self, percent: i64) -> Self {
+        self.options.critical = percent;
+        self
+    }
+
+    /// Use the BrandMeister API at `url` instead of the public one, e.g. a local proxy.
+    pub fn api_url(mut self, url: impl Into<String>) -> Self {
+        self.options.api_url = url.into();
+        self
+    }
+
+    /// Perform the HTTP requests with `transport` instead of the default one from the
+    /// `ureq` feature.
+    pub fn transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.options.transport = Some(transport);
+        self
+    }
+
+    /// Validate the configuration.
+    pub fn build(self) -> Result<FleetCheckOptions> {
+        let mut opts = self.options;
+        opts.master = self
+            .master
+            .ok_or_else(|| invalid("master id is required"))?;
+        if opts.stale_after < Duration::zero() || opts.stale_after >= opts.ignore_after {
+            return Err(invalid(format!(
+                "stale time {} must be positive and lower than ignore time {}",
+                opts.stale_after.num_seconds(),
+                opts.ignore_after.num_seconds()
+            )));
+        }
+        if !(0..=100).contains(&opts.warning) || !(0..=100).contains(&opts.critical) {
+            return Err(invalid("percentage thresholds must be between 0 and 100"));
+        }
+        if opts.warning > opts.critical {
+            return Err(invalid(format!(
+                "warning threshold {}% is greater than critical threshold {}%",
+                opts.warning, opts.critical
+            )));
+        }
+        Ok(opts)
+    }
+}
+
+/// Check how many of the repeaters connected to the master have gone stale recently.
+///
+/// Repeaters inactive for longer than the ignore time are left out, so that decommissioned
+/// ones do not keep the master in alarm. Failures are reported as an UNKNOWN outcome.
+pub fn check_master_fleet(opts: &FleetCheckOptions) -> CheckOutcome {
+    let name = format!("BrandMeister master {}", opts.master);
+    match run(opts, name.clone()) {
+        Ok(outcome) => outcome,
+        Err(e) => CheckOutcome::failed(name, &e),
+    }
+}
+
+fn run(opts: &FleetCheckOptions, name: String) -> Result<CheckOutcome> {
+    let client = match &opts.transport {
+        Some(transport) => BmClient::with_transport(transport.clone()),
+        None => BmClient::with_default_transport()?,
+    }
+    .with_api_url(&opts.api_url);
+    let now = Utc::now();
+    let inactive: Vec<Duration> = client
+        .master_last_seen(opts.master)?
+        .iter()
+        .map(|last_seen| now.signed_duration_since(*last_seen))
+        .filter(|&elapsed| elapsed < opts.ignore_after)
+        .collect();
+    if inactive.is_empty() {
+        return Err(Error::NotFound(format!(
+            "master {} has no repeaters seen recently",
+            opts.master
+        )));
+    }
+    let total = inactive.len() as i64;
+    let stale = inactive
+        .iter()
+        .filter(|&&elapsed| elapsed >= opts.stale_after)
+        .count() as i64;
+    let percent = stale * 100 / total;
+    Ok(CheckOutcome::new(
+        name,
+        format!("{} of {} repeaters stale", stale, total),
+        vec![
+            PerfData::count("stale_repeaters", stale),
+            PerfData::percent("stale_percent", percent, opts.warning, opts.critical),
+        ],
+    ))
+}
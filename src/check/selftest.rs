@@ -0,0 +1,221 @@
+//! Self-test of the plugin environment, for post-install verification.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+
+use super::{CheckOutcome, NagiosState, PerfData};
+use crate::client::API_URL;
+use crate::state::write_atomic;
+use crate::transport::default_transport;
+use crate::{aprs, Error, Result, Transport};
+
+/// Largest difference with the API server clock before the local clock is reported wrong.
+const MAX_CLOCK_SKEW: i64 = 60;
+
+/// Callsign looked up to validate an aprs.fi API key when no callsign is given.
+const APRS_TEST_CALL: &str = "OH7RDA";
+
+/// What to verify in a [selftest], built like a [CheckOptions](super::CheckOptions) but
+/// without validation since every setting is optional.
+///
+/// Example:
+/// ```no_run
+/// use brandmeister::check::{selftest, SelftestOptions};
+/// let outcome = selftest(&SelftestOptions::new().cache_dir("/var/cache/check_brandmeister"));
+/// println!("{}: {}", outcome.state, outcome.description);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SelftestOptions {
+    cache_dir: Option<PathBuf>,
+    state_dir: Option<PathBuf>,
+    aprs_call: Option<String>,
+    aprs_apikey: Option<String>,
+    api_url: Option<String>,
+    api_transport: Option<Arc<dyn Transport>>,
+    transport: Option<Arc<dyn Transport>>,
+}
+
+impl SelftestOptions {
+    /// Verify API reachability and the clock only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Verify that `dir` can be used with `--cache-dir`.
+    pub fn cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Verify that `dir` can be used with `--state-dir`.
+    pub fn state_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.state_dir = Some(dir.into());
+        self
+    }
+
+    /// Verify that aprs.fi accepts `api_key`, looking up `call` if given.
+    pub fn aprs(mut self, call: Option<String>, api_key: impl Into<String>) -> Self {
+        self.aprs_call = call;
+        self.aprs_apikey = Some(api_key.into());
+        self
+    }
+
+    /// Verify the API at `url` instead of the public one, e.g. a local proxy, reached with
+    /// `transport` if given instead of the one of the other requests, e.g. a Unix socket.
+    pub fn api(mut self, url: impl Into<String>, transport: Option<Arc<dyn Transport>>) -> Self {
+        self.api_url = Some(url.into().trim_end_matches('/').to_string());
+        self.api_transport = transport;
+        self
+    }
+
+    /// Perform the HTTP requests with `transport` instead of the default one from the
+    /// `ureq` feature.
+    pub fn transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+}
+
+/// Result of one verification.
+struct Step {
+    state: NagiosState,
+    message: String,
+}
+
+impl Step {
+    fn ok(message: String) -> Self {
+        Step {
+            state: NagiosState::Ok,
+            message,
+        }
+    }
+
+    fn failed(state: NagiosState, message: String) -> Self {
+        Step { state, message }
+    }
+}
+
+/// Verify that the plugin can run as configured by `opts`: API reachability, clock, aprs.fi
+/// credentials and cache and state directories.
+///
+/// Each verification is reported as a message. The outcome is CRITICAL when one failed and
+/// WARNING when the clock is off.
+pub fn selftest(opts: &SelftestOptions) -> CheckOutcome {
+    let name = "check_brandmeister selftest".to_string();
+    let transport = match &opts.transport {
+        Some(transport) => transport.clone(),
+        None => match default_transport() {
+            Ok(transport) => transport,
+            Err(e) => return CheckOutcome::failed(name, &e),
+        },
+    };
+
+    let mut steps = Vec::new();
+    let mut perfdata = Vec::new();
+    let url = format!("{}/", opts.api_url.as_deref().unwrap_or(API_URL));
+    let api_transport = opts.api_transport.as_ref().unwrap_or(&transport);
+    let start = Instant::now();
+    match api_transport.get(&url, &[]) {
+        Ok(response) if response.status < 500 => {
+            let elapsed = start.elapsed().as_millis() as i64;
+            perfdata.push(PerfData {
+                unit: "ms".to_string(),
+                ..PerfData::count("api_rtt_ms", elapsed)
+            });
+            steps.push(Step::ok(format!("API reachable in {}ms", elapsed)));
+            steps.push(clock_step(response.header("Date")));
+        }
+        Ok(response) => steps.push(Step::failed(
+            NagiosState::Critical,
+            format!("API unavailable: {} answered {}", url, response.status),
+        )),
+        Err(e) => steps.push(Step::failed(
+            NagiosState::Critical,
+            format!("API unreachable: {}", e),
+        )),
+    }
+    if let Some(key) = &opts.aprs_apikey {
+        let call = opts.aprs_call.as_deref().unwrap_or(APRS_TEST_CALL);
+        steps.push(
+            match aprs::last_heard_seconds_with(transport.as_ref(), call, key) {
+                Ok(_) | Err(Error::NotFound(_)) => Step::ok("aprs.fi API key accepted".to_string()),
+                Err(e) => Step::failed(NagiosState::Critical, format!("aprs.fi: {}", e)),
+            },
+        );
+    }
+    for (what, dir) in [("cache", &opts.cache_dir), ("state", &opts.state_dir)] {
+        if let Some(dir) = dir {
+            steps.push(match verify_writable(dir) {
+                Ok(()) => Step::ok(format!("{} directory {} is writable", what, dir.display())),
+                Err(e) => Step::failed(NagiosState::Critical, format!("{} directory: {}", what, e)),
+            });
+        }
+    }
+
+    let state = steps
+        .iter()
+        .fold(NagiosState::Ok, |state, step| state.worst(step.state));
+    let passed = steps.iter().filter(|s| s.state == NagiosState::Ok).count();
+    CheckOutcome {
+        state,
+        name,
+        description: format!("{} of {} checks passed", passed, steps.len()),
+        messages: steps.into_iter().map(|s| s.message).collect(),
+        perfdata,
+        last_seen: None,
+        elapsed: None,
+        escalated: false,
+        error: None,
+    }
+}
+
+/// Compare the local clock with the `Date` header of the API server.
+fn clock_step(date: Option<&str>) -> Step {
+    let Some(server) = date.and_then(|d| DateTime::parse_from_rfc2822(d).ok()) else {
+        return Step::ok("clock not verified, the API sent no date".to_string());
+    };
+    let skew = Utc::now().signed_duration_since(server).num_seconds();
+    if skew.abs() > MAX_CLOCK_SKEW {
+        Step::failed(
+            NagiosState::Warning,
+            format!("local clock is {}s off the API server clock", skew),
+        )
+    } else {
+        Step::ok(format!(
+            "clock within {}s of the API server",
+            MAX_CLOCK_SKEW
+        ))
+    }
+}
+
+/// Create `dir` if needed and write and remove a file in it.
+fn verify_writable(dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir).map_err(|e| Error::io(format!("creating {}", dir.display()), e))?;
+    let probe = dir.join(".selftest");
+    write_atomic(&probe, "")?;
+    fs::remove_file(&probe).map_err(|e| Error::io(format!("removing {}", probe.display()), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::stub::StubTransport;
+
+    #[test]
+    fn selftest_probes_the_configured_api() {
+        let api = Arc::new(StubTransport::new().with("http://proxy:8080/v2/", 200, "{}"));
+        let other = Arc::new(StubTransport::new());
+        let opts = SelftestOptions::new()
+            .transport(other.clone())
+            .api("http://proxy:8080/v2/", Some(api.clone()));
+        let outcome = selftest(&opts);
+        assert_eq!(outcome.state, NagiosState::Ok, "{:?}", outcome.messages);
+        assert_eq!(api.urls(), ["http://proxy:8080/v2/"]);
+        assert!(other.urls().is_empty());
+        assert_eq!(outcome.perfdata[0].unit, "ms");
+    }
+}
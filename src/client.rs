@@ -0,0 +1,760 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::cache::{Cache, Validated};
+use crate::duration;
+use crate::retry::RetryPolicy;
+use crate::transport::{default_transport, encode_query, excerpt, parse_json, Transport};
+use crate::{Error, Result, Timings};
+
+pub(crate) const API_URL: &str = "https://api.brandmeister.network/v2";
+/// Default limit on the size of API responses, well above the largest device lists.
+pub(crate) const MAX_RESPONSE_SIZE: usize = 4 << 20;
+
+#[derive(Debug, Deserialize)]
+struct RepeaterStatus {
+    last_seen: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CallsignDevice {
+    id: u32,
+    last_seen: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RepeaterStatusCode {
+    status: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RepeaterMaster {
+    last_known_master: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RepeaterProfile {
+    #[serde(default)]
+    static_subscriptions: Vec<serde_json::Value>,
+    #[serde(default)]
+    dynamic_subscriptions: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RepeaterPosition {
+    lat: Option<f64>,
+    lng: Option<f64>,
+}
+
+/// Descriptive details of a repeater, as registered in BrandMeister SelfCare.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepeaterMetadata {
+    /// Callsign of the repeater, e.g. ON0ABC
+    pub callsign: Option<String>,
+    /// City where the repeater is located
+    pub city: Option<String>,
+}
+
+impl RepeaterMetadata {
+    /// Short human readable label, e.g. `ON0ABC (Liège)`, if the callsign is known.
+    pub fn label(&self) -> Option<String> {
+        let callsign = self.callsign.as_deref().filter(|c| !c.trim().is_empty())?;
+        Some(
+            match self.city.as_deref().filter(|c| !c.trim().is_empty()) {
+                Some(city) => format!("{} ({})", callsign.trim(), city.trim()),
+                None => callsign.trim().to_string(),
+            },
+        )
+    }
+}
+
+/// Hardware, firmware, frequencies and location of a repeater, as registered in BrandMeister.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RepeaterInventory {
+    /// BrandMeister id of the repeater
+    #[serde(default)]
+    pub id: u32,
+    /// Callsign of the repeater, e.g. ON0ABC
+    pub callsign: Option<String>,
+    /// Hardware model, e.g. MMDVM
+    pub hardware: Option<String>,
+    /// Firmware version
+    pub firmware: Option<String>,
+    /// Transmit frequency in MHz
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub tx: Option<String>,
+    /// Receive frequency in MHz
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub rx: Option<String>,
+    /// DMR color code
+    pub colorcode: Option<i64>,
+    /// City where the repeater is located
+    pub city: Option<String>,
+    /// Latitude in degrees
+    pub lat: Option<f64>,
+    /// Longitude in degrees
+    pub lng: Option<f64>,
+}
+
+/// Deserialize a value the API sends either as a string or as a number, e.g. frequencies.
+fn string_or_number<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<String>, D::Error> {
+    Ok(
+        match Option::<serde_json::Value>::deserialize(deserializer)? {
+            Some(serde_json::Value::String(s)) => Some(s),
+            Some(serde_json::Value::Number(n)) => Some(n.to_string()),
+            _ => None,
+        },
+    )
+}
+
+/// Name of the cache entries of the API resource at `path`.
+/// A device record and the time it took to fetch it.
+type DeviceRecord = (serde_json::Value, std::time::Duration);
+
+fn cache_key(path: &str) -> String {
+    path.replace(|c: char| !c.is_ascii_alphanumeric(), "-")
+}
+
+fn parse_last_seen(last_seen: &str) -> Result<DateTime<Utc>> {
+    let naive = NaiveDateTime::parse_from_str(last_seen, "%Y-%m-%d %H:%M:%S").map_err(|e| {
+        Error::parse(
+            "brandmeister last seen time",
+            format!("{}: {:?}", e, last_seen),
+        )
+    })?;
+    Ok(Utc.from_utc_datetime(&naive))
+}
+
+/// Client for the parts of the BrandMeister API v2 used by this crate.
+///
+/// Example:
+/// ```no_run
+/// use brandmeister::{BmClient, Cache};
+/// let client = BmClient::new().with_cache(Cache::new("/var/cache/check_brandmeister", 60));
+/// let seconds :i64 = client.last_seen_seconds(270107).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct BmClient {
+    transport: Arc<dyn Transport>,
+    api_url: String,
+    cache: Option<Cache>,
+    retry: RetryPolicy,
+    max_response_size: usize,
+    strict_schema: bool,
+    server_date: Arc<Mutex<Option<DateTime<Utc>>>>,
+    timings: Option<Arc<Timings>>,
+    /// Device records fetched so far with the time it took, when reused by the accessors
+    device_records: Option<Arc<Mutex<HashMap<u32, DeviceRecord>>>>,
+}
+
+#[cfg(feature = "ureq")]
+impl Default for BmClient {
+    fn default() -> Self {
+        BmClient::with_transport(Arc::new(crate::UreqTransport::default()))
+    }
+}
+
+impl BmClient {
+    /// Create a client without cache, using ureq.
+    #[cfg(feature = "ureq")]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a client without cache, performing requests with `transport`.
+    pub fn with_transport(transport: Arc<dyn Transport>) -> Self {
+        BmClient {
+            transport,
+            api_url: API_URL.to_string(),
+            cache: None,
+            retry: RetryPolicy::default(),
+            max_response_size: MAX_RESPONSE_SIZE,
+            strict_schema: false,
+            server_date: Arc::default(),
+            timings: None,
+            device_records: None,
+        }
+    }
+
+    /// Create a client with the transport from the `ureq` feature, if enabled.
+    pub(crate) fn with_default_transport() -> Result<Self> {
+        Ok(BmClient::with_transport(default_transport()?))
+    }
+
+    pub(crate) fn transport(&self) -> &dyn Transport {
+        self.transport.as_ref()
+    }
+
+    /// Use the API at `url` instead of the public one, e.g. a local proxy.
+    pub fn with_api_url(mut self, url: impl Into<String>) -> Self {
+        self.api_url = url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Share API responses with other invocations through `cache`.
+    pub fn with_cache(mut self, cache: Cache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Retry requests failing with a transient error according to `policy`.
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Reject API responses larger than `bytes`, 4 MiB by default.
+    pub fn with_max_response_size(mut self, bytes: usize) -> Self {
+        self.max_response_size = bytes;
+        self
+    }
+
+    /// Require API responses to be declared as JSON, and device lookups to answer a single
+    /// record, instead of making the most of unexpected payloads.
+    pub fn with_strict_schema(mut self, strict: bool) -> Self {
+        self.strict_schema = strict;
+        self
+    }
+
+    /// Record the time to parse each API response in `timings`.
+    pub fn with_timings(mut self, timings: Arc<Timings>) -> Self {
+        self.timings = Some(timings);
+        self
+    }
+
+    /// Fetch each device record once and answer all the accessors of the device from it, for
+    /// clients living as long as a single check. Later changes of the record are not seen.
+    pub(crate) fn with_device_reuse(mut self) -> Self {
+        self.device_records = Some(Arc::default());
+        self
+    }
+
+    /// GET `url`, sending the validators of `previous` and returning it again if the server
+    /// answers 304 Not Modified.
+    fn get_conditional(&self, url: &str, previous: Option<&Validated>) -> Result<Validated> {
+        let mut headers = Vec::new();
+        if let Some(previous) = previous {
+            if let Some(etag) = &previous.etag {
+                headers.push(("If-None-Match", etag.as_str()));
+            }
+            if let Some(last_modified) = &previous.last_modified {
+                headers.push(("If-Modified-Since", last_modified.as_str()));
+            }
+        }
+        let response = self
+            .transport
+            .get_limited(url, &headers, self.max_response_size)?;
+        if let Some(date) = response
+            .header("Date")
+            .and_then(|d| DateTime::parse_from_rfc2822(d).ok())
+        {
+            *self.server_date.lock().unwrap_or_else(|e| e.into_inner()) = Some(date.into());
+        }
+        match (response.status, previous) {
+            (304, Some(previous)) => return Ok(previous.clone()),
+            (status, _) if status >= 400 || status == 304 => {
+                return Err(Error::Http {
+                    url: url.to_string(),
+                    status,
+                })
+            }
+            _ => {}
+        }
+        let what = format!("brandmeister API result for {}", url);
+        if response.body.len() > self.max_response_size {
+            return Err(Error::parse(
+                what,
+                format!(
+                    "the response exceeds the limit of {} bytes",
+                    self.max_response_size
+                ),
+            ));
+        }
+        if self.strict_schema {
+            let content_type = response.header("Content-Type").unwrap_or("none");
+            let media_type = content_type.split(';').next().unwrap_or_default().trim();
+            let json = media_type.eq_ignore_ascii_case("application/json")
+                || media_type.to_ascii_lowercase().ends_with("+json");
+            if !json {
+                return Err(Error::parse(
+                    what,
+                    format!(
+                        "expected JSON but the content type is {}; response: {}",
+                        content_type,
+                        excerpt(&response.body)
+                    ),
+                ));
+            }
+        }
+        Ok(Validated {
+            etag: response.header("ETag").map(str::to_string),
+            last_modified: response.header("Last-Modified").map(str::to_string),
+            body: response.body,
+        })
+    }
+
+    /// Date sent by the API server with the last response received from it, if any. Responses
+    /// served from the cache are not received from the server.
+    pub fn server_date(&self) -> Option<DateTime<Utc>> {
+        *self.server_date.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Get the body of the API resource at `path`, from the cache if enabled.
+    ///
+    /// With a cache, the response of the previous window is revalidated with a conditional
+    /// request, and reused if the API reports it unchanged.
+    fn get(&self, path: &str) -> Result<String> {
+        let request_url = format!("{}/{}", self.api_url, path);
+        let Some(cache) = &self.cache else {
+            return self
+                .retry
+                .run(|| Ok(self.get_conditional(&request_url, None)?.body));
+        };
+        let key = cache_key(path);
+        cache.get_or_fetch(&key, || {
+            let previous = cache.validated(&key);
+            let response = self
+                .retry
+                .run(|| self.get_conditional(&request_url, previous.as_ref()))?;
+            if response.etag.is_some() || response.last_modified.is_some() {
+                cache.store_validated(&key, &response)?;
+            }
+            Ok(response.body)
+        })
+    }
+
+    /// Get and deserialize the API resource at `path`.
+    fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let body = self.get(path)?;
+        let start = Instant::now();
+        let parsed = parse_json(&format!("brandmeister API result for {}", path), &body);
+        if let Some(timings) = &self.timings {
+            let url = format!("{}/{}", self.api_url, path);
+            timings.record(&url, "parse", start.elapsed());
+        }
+        parsed
+    }
+
+    /// Get and deserialize the device record of `repeater_id`, reusing the one fetched before
+    /// if enabled.
+    fn get_device<T: DeserializeOwned>(&self, repeater_id: u32) -> Result<T> {
+        let what = format!("brandmeister API result for device/{}", repeater_id);
+        let Some(records) = &self.device_records else {
+            return parse_json(&what, &self.device_record_value(repeater_id)?.to_string());
+        };
+        let reused = records
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&repeater_id)
+            .map(|(record, _)| record.clone());
+        let record = match reused {
+            Some(record) => record,
+            None => {
+                let start = std::time::Instant::now();
+                let record = self.device_record_value(repeater_id)?;
+                records
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .insert(repeater_id, (record.clone(), start.elapsed()));
+                record
+            }
+        };
+        parse_json(&what, &record.to_string())
+    }
+
+    /// Return the time it took to fetch the device record of `repeater_id`, if reused and
+    /// fetched already, as reading it again takes no time.
+    pub(crate) fn device_fetch_time(&self, repeater_id: u32) -> Option<std::time::Duration> {
+        let records = self.device_records.as_ref()?;
+        let records = records.lock().unwrap_or_else(|e| e.into_inner());
+        records.get(&repeater_id).map(|&(_, elapsed)| elapsed)
+    }
+
+    /// Get the device record of `repeater_id`.
+    ///
+    /// With a cache, a device that does not exist is reported as not found without querying
+    /// the API again for [NOT_FOUND_TTL](crate::cache::NOT_FOUND_TTL), after which the API is
+    /// asked again in case the device has been registered since.
+    fn device_record_value(&self, repeater_id: u32) -> Result<serde_json::Value> {
+        let path = format!("device/{}", repeater_id);
+        let key = cache_key(&path);
+        if let Some(age) = self.cache.as_ref().and_then(|cache| cache.not_found(&key)) {
+            return Err(Error::NotFound(format!(
+                "repeater {} was not found on BrandMeister {} ago, verify the repeater id",
+                repeater_id,
+                duration::format_duration(age)
+            )));
+        }
+        let result = self.fetch_device(&path, repeater_id);
+        let missing = matches!(
+            result,
+            Err(Error::Http { status: 404, .. }) | Err(Error::NotFound(_))
+        );
+        if let Some(cache) = &self.cache {
+            cache.store_not_found(&key, missing)?;
+        }
+        if missing {
+            return Err(Error::NotFound(format!(
+                "repeater {} not found on BrandMeister, verify the repeater id",
+                repeater_id
+            )));
+        }
+        result
+    }
+
+    /// Get the device record at `path`.
+    ///
+    /// Some endpoints answer with an array of matches instead of a single object: the record
+    /// whose id is exactly `repeater_id` is selected, and it is an error if there is none or
+    /// several, or with a strict schema. A single object with another id is rejected too.
+    fn fetch_device(&self, path: &str, repeater_id: u32) -> Result<serde_json::Value> {
+        let what = format!("brandmeister API result for {}", path);
+        let value: serde_json::Value = self.get_json(path)?;
+        let has_id = |record: &serde_json::Value| {
+            record.get("id").and_then(serde_json::Value::as_u64) == Some(u64::from(repeater_id))
+        };
+        match value {
+            serde_json::Value::Array(_) if self.strict_schema => Err(Error::parse(
+                what,
+                "expected a device record but the response is an array",
+            )),
+            serde_json::Value::Array(records) => {
+                let mut matches: Vec<_> = records.into_iter().filter(has_id).collect();
+                match matches.len() {
+                    0 => Err(Error::NotFound(format!(
+                        "no device with id {} in the API result",
+                        repeater_id
+                    ))),
+                    1 => Ok(matches.remove(0)),
+                    n => Err(Error::Api(format!(
+                        "{} devices with id {} in the API result",
+                        n, repeater_id
+                    ))),
+                }
+            }
+            record if record.get("id").is_none() || has_id(&record) => Ok(record),
+            record => Err(Error::Api(format!(
+                "the API returned device {} instead of {}",
+                record["id"], repeater_id
+            ))),
+        }
+    }
+
+    fn get_repeater_last_update(&self, repeater_id: u32) -> Result<String> {
+        let status: RepeaterStatus = self.get_device(repeater_id)?;
+        Ok(status.last_seen)
+    }
+
+    /// Return the time the repeater was last seen online on BrandMeister.
+    pub fn last_seen_at(&self, repeater_id: u32) -> Result<DateTime<Utc>> {
+        parse_last_seen(&self.get_repeater_last_update(repeater_id)?)
+    }
+
+    /// Return the time elapsed since the repeater was seen online on BrandMeister.
+    pub fn last_seen_elapsed(&self, repeater_id: u32) -> Result<Duration> {
+        let last_update = self.last_seen_at(repeater_id)?;
+        Ok(Utc::now().signed_duration_since(last_update))
+    }
+
+    /// Return the number of seconds since the repeater was seen online on BrandMeister.
+    pub fn last_seen_seconds(&self, repeater_id: u32) -> Result<i64> {
+        Ok(self.last_seen_elapsed(repeater_id)?.num_seconds())
+    }
+
+    /// Return the callsign and city of the repeater.
+    pub fn metadata(&self, repeater_id: u32) -> Result<RepeaterMetadata> {
+        self.get_device(repeater_id)
+    }
+
+    /// Return the full device record of the repeater, as sent by the API.
+    pub fn device_record(
+        &self,
+        repeater_id: u32,
+    ) -> Result<serde_json::Map<String, serde_json::Value>> {
+        self.get_device(repeater_id)
+    }
+
+    /// Return the hardware, firmware, frequencies and location of the repeater.
+    pub fn inventory(&self, repeater_id: u32) -> Result<RepeaterInventory> {
+        let inventory: RepeaterInventory = self.get_device(repeater_id)?;
+        Ok(RepeaterInventory {
+            id: repeater_id,
+            ..inventory
+        })
+    }
+
+    /// Return the latitude and longitude of the repeater, if registered.
+    pub fn position(&self, repeater_id: u32) -> Result<Option<(f64, f64)>> {
+        let position: RepeaterPosition = self.get_device(repeater_id)?;
+        Ok(position.lat.zip(position.lng))
+    }
+
+    /// Return the status code reported for the repeater, if any.
+    pub fn status_code(&self, repeater_id: u32) -> Result<Option<i64>> {
+        let status: RepeaterStatusCode = self.get_device(repeater_id)?;
+        Ok(status.status)
+    }
+
+    /// Return the number of static and dynamic talkgroups the repeater is linked to.
+    pub fn linked_talkgroup_counts(&self, repeater_id: u32) -> Result<(usize, usize)> {
+        let profile: RepeaterProfile = self.get_json(&format!("device/{}/profile", repeater_id))?;
+        Ok((
+            profile.static_subscriptions.len(),
+            profile.dynamic_subscriptions.len(),
+        ))
+    }
+
+    /// Return the master server the repeater is or was last connected to, if any.
+    pub fn last_known_master(&self, repeater_id: u32) -> Result<Option<u32>> {
+        let master: RepeaterMaster = self.get_device(repeater_id)?;
+        Ok(master.last_known_master)
+    }
+
+    /// Return the number of static talkgroups configured on the repeater.
+    pub fn static_talkgroup_count(&self, repeater_id: u32) -> Result<usize> {
+        let talkgroups: Vec<serde_json::Value> =
+            self.get_json(&format!("device/{}/talkgroup", repeater_id))?;
+        Ok(talkgroups.len())
+    }
+
+    /// Return the id and last seen time of each device registered with `callsign`.
+    pub fn devices_by_callsign(&self, callsign: &str) -> Result<Vec<(u32, DateTime<Utc>)>> {
+        let path = format!("device/byCall?callsign={}", encode_query(callsign));
+        // A single match may come as an object instead of an array.
+        let devices = match self.get_json(&path)? {
+            serde_json::Value::Array(devices) => devices,
+            device => vec![device],
+        };
+        devices
+            .into_iter()
+            .map(|device| {
+                let device: CallsignDevice = parse_json(
+                    &format!("brandmeister API result for {}", path),
+                    &device.to_string(),
+                )?;
+                Ok((device.id, parse_last_seen(&device.last_seen)?))
+            })
+            .collect()
+    }
+
+    /// Return the time each device connected to the master server was last seen online.
+    pub fn master_last_seen(&self, master_id: u32) -> Result<Vec<DateTime<Utc>>> {
+        let devices: Vec<RepeaterStatus> =
+            self.get_json(&format!("device/byMaster/{}", master_id))?;
+        devices
+            .iter()
+            .map(|device| parse_last_seen(&device.last_seen))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::{Validated, NOT_FOUND_TTL};
+    use crate::state::test_dir;
+    use crate::transport::stub::StubTransport;
+
+    fn client(transport: &Arc<StubTransport>) -> BmClient {
+        BmClient::with_transport(transport.clone()).with_api_url("http://stub")
+    }
+
+    /// Device record of `id` registered with `callsign`.
+    fn device(id: u32, callsign: &str) -> String {
+        format!(
+            r#"{{"id": {}, "callsign": "{}", "last_seen": "2026-10-14 05:22:15"}}"#,
+            id, callsign
+        )
+    }
+
+    #[test]
+    fn array_with_the_wrong_and_the_right_id() {
+        let records = format!(
+            "[{}, {}]",
+            device(270108, "ON0XYZ"),
+            device(270107, "ON0ABC")
+        );
+        let stub = Arc::new(StubTransport::new().with("device/270107", 200, &records));
+        let metadata = client(&stub).metadata(270107).unwrap();
+        assert_eq!(metadata.callsign.as_deref(), Some("ON0ABC"));
+    }
+
+    #[test]
+    fn array_without_the_right_id() {
+        let records = format!("[{}]", device(270108, "ON0XYZ"));
+        let stub = Arc::new(StubTransport::new().with("device/270107", 200, &records));
+        let e = client(&stub).metadata(270107).unwrap_err();
+        assert!(matches!(e, Error::NotFound(_)), "{:?}", e);
+        let empty = Arc::new(StubTransport::new().with("device/270107", 200, "[]"));
+        let e = client(&empty).metadata(270107).unwrap_err();
+        assert!(matches!(e, Error::NotFound(_)), "{:?}", e);
+    }
+
+    #[test]
+    fn array_with_the_right_id_twice() {
+        let record = device(270107, "ON0ABC");
+        let records = format!("[{}, {}]", record, record);
+        let stub = Arc::new(StubTransport::new().with("device/270107", 200, &records));
+        let e = client(&stub).metadata(270107).unwrap_err();
+        assert_eq!(e.to_string(), "2 devices with id 270107 in the API result");
+    }
+
+    #[test]
+    fn record_with_the_wrong_id() {
+        let stub = Arc::new(StubTransport::new().with("device/270107", 200, &device(270108, "X")));
+        let e = client(&stub).metadata(270107).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "the API returned device 270108 instead of 270107"
+        );
+    }
+
+    #[test]
+    fn strict_schema_rejects_arrays() {
+        let records = format!("[{}]", device(270107, "ON0ABC"));
+        let stub = Arc::new(StubTransport::new().with_headers(
+            "device/270107",
+            200,
+            &[("Content-Type", "application/json")],
+            &records,
+        ));
+        let e = client(&stub)
+            .with_strict_schema(true)
+            .metadata(270107)
+            .unwrap_err();
+        assert!(e.to_string().contains("the response is an array"), "{}", e);
+    }
+
+    const LAST_MODIFIED: &str = "Wed, 14 Oct 2026 05:22:15 GMT";
+
+    #[test]
+    fn validators_are_stored_with_the_response() {
+        let cache = Cache::new(test_dir("validators"), 60);
+        let stub = Arc::new(StubTransport::new().with_headers(
+            "device/270107",
+            200,
+            &[("ETag", "\"v1\""), ("Last-Modified", LAST_MODIFIED)],
+            &device(270107, "ON0ABC"),
+        ));
+        client(&stub)
+            .with_cache(cache.clone())
+            .metadata(270107)
+            .unwrap();
+        let validated = cache.validated("device-270107").unwrap();
+        assert_eq!(validated.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(validated.last_modified.as_deref(), Some(LAST_MODIFIED));
+        assert_eq!(validated.body, device(270107, "ON0ABC"));
+    }
+
+    #[test]
+    fn not_modified_reuses_the_cached_body_and_etag() {
+        let cache = Cache::new(test_dir("not_modified"), 60);
+        let previous = Validated {
+            etag: Some("\"v1\"".to_string()),
+            last_modified: Some(LAST_MODIFIED.to_string()),
+            body: device(270107, "ON0ABC"),
+        };
+        cache.store_validated("device-270107", &previous).unwrap();
+        let stub = Arc::new(StubTransport::new().with("device/270107", 304, ""));
+        let metadata = client(&stub)
+            .with_cache(cache.clone())
+            .metadata(270107)
+            .unwrap();
+
+        assert_eq!(metadata.callsign.as_deref(), Some("ON0ABC"));
+        let sent = &stub.headers()[0];
+        assert!(sent.contains(&("If-None-Match".to_string(), "\"v1\"".to_string())));
+        assert!(sent.contains(&("If-Modified-Since".to_string(), LAST_MODIFIED.to_string())));
+        let validated = cache.validated("device-270107").unwrap();
+        assert_eq!(validated.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(validated.body, previous.body);
+    }
+
+    #[test]
+    fn not_modified_without_a_cached_response() {
+        let stub = Arc::new(StubTransport::new().with("device/270107", 304, ""));
+        let e = client(&stub).metadata(270107).unwrap_err();
+        assert!(matches!(e, Error::Http { status: 304, .. }), "{:?}", e);
+        assert!(stub.headers()[0].is_empty());
+    }
+
+    #[test]
+    fn not_found_is_served_from_the_cache() {
+        let cache = Cache::new(test_dir("not_found"), 60);
+        let stub = Arc::new(StubTransport::new().with("device/270107", 404, "").with(
+            "device/270107",
+            200,
+            &device(270107, "ON0ABC"),
+        ));
+        let e = client(&stub)
+            .with_cache(cache.clone())
+            .metadata(270107)
+            .unwrap_err();
+        assert!(e.to_string().contains("verify the repeater id"), "{}", e);
+
+        let e = client(&stub)
+            .with_cache(cache.clone())
+            .metadata(270107)
+            .unwrap_err();
+        assert!(
+            e.to_string()
+                .starts_with("repeater 270107 was not found on BrandMeister "),
+            "{}",
+            e
+        );
+        assert_eq!(stub.urls().len(), 1);
+    }
+
+    #[test]
+    fn not_found_is_refetched_after_its_ttl() {
+        let dir = test_dir("not_found_ttl");
+        let cache = Cache::new(&dir, 60);
+        let stub =
+            Arc::new(StubTransport::new().with("device/270107", 200, &device(270107, "ON0ABC")));
+        let expired = Utc::now() - NOT_FOUND_TTL;
+        std::fs::write(
+            dir.join("device-270107.not-found"),
+            expired.timestamp().to_string(),
+        )
+        .unwrap();
+        assert!(cache.not_found("device-270107").is_none());
+
+        let metadata = client(&stub).with_cache(cache).metadata(270107).unwrap();
+        assert_eq!(metadata.callsign.as_deref(), Some("ON0ABC"));
+        assert_eq!(stub.urls(), ["http://stub/device/270107"]);
+        assert!(!dir.join("device-270107.not-found").exists());
+    }
+
+    #[test]
+    fn not_found_within_its_ttl() {
+        let dir = test_dir("not_found_within");
+        let cache = Cache::new(&dir, 60);
+        let stub =
+            Arc::new(StubTransport::new().with("device/270107", 200, &device(270107, "ON0ABC")));
+        let recent = Utc::now() - NOT_FOUND_TTL + Duration::seconds(30);
+        std::fs::write(
+            dir.join("device-270107.not-found"),
+            recent.timestamp().to_string(),
+        )
+        .unwrap();
+        let e = client(&stub)
+            .with_cache(cache)
+            .metadata(270107)
+            .unwrap_err();
+        assert!(
+            e.to_string().contains("was not found on BrandMeister 9m"),
+            "{}",
+            e
+        );
+        assert!(stub.urls().is_empty());
+    }
+}
@@ -0,0 +1,92 @@
+//! Parsing and formatting of human friendly durations such as `90s` or `2h30m`.
+
+use chrono::Duration;
+
+/// Parse a sequence of numbers with `ms`, `s`, `m`, `h` or `d` units, or a plain number in
+/// `plain_unit`. Durations are never negative.
+///
+/// Example:
+/// ```
+/// use brandmeister::duration;
+/// use chrono::Duration;
+/// assert_eq!(duration::parse("2h30m", Duration::seconds(1)), Ok(Duration::minutes(150)));
+/// assert_eq!(duration::parse("90", Duration::seconds(1)), Ok(Duration::seconds(90)));
+/// assert!(duration::parse("-90", Duration::seconds(1)).is_err());
+/// ```
+pub fn parse(s: &str, plain_unit: Duration) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration {}, expected e.g. 90s, 10m or 2h30m", s);
+    if let Ok(value) = s.trim().parse::<u32>() {
+        return plain_unit
+            .num_milliseconds()
+            .checked_mul(i64::from(value))
+            .map(Duration::milliseconds)
+            .ok_or_else(invalid);
+    }
+    let mut total = Duration::zero();
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let value: i64 = rest[..digits].parse().map_err(|_| invalid())?;
+        rest = &rest[digits..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let unit = match &rest[..unit_len] {
+            "ms" => Duration::milliseconds(1),
+            "s" => Duration::seconds(1),
+            "m" | "min" => Duration::minutes(1),
+            "h" => Duration::hours(1),
+            "d" => Duration::days(1),
+            _ => return Err(invalid()),
+        };
+        rest = &rest[unit_len..];
+        total = value
+            .checked_mul(unit.num_milliseconds())
+            .and_then(|ms| total.checked_add(&Duration::milliseconds(ms)))
+            .ok_or_else(invalid)?;
+    }
+    Ok(total)
+}
+
+/// Format `seconds` with its two most significant units, e.g. `42m`, `2h5m` or `3d4h`.
+///
+/// Example:
+/// ```
+/// use brandmeister::duration;
+/// assert_eq!(duration::format(7500), "2h5m");
+/// ```
+pub fn format(seconds: i64) -> String {
+    format_duration(Duration::seconds(seconds))
+}
+
+/// Format `duration` with its two most significant units, e.g. `42m`, `2h5m` or `3d4h`,
+/// ignoring fractions of a second.
+///
+/// Example:
+/// ```
+/// use brandmeister::duration;
+/// use chrono::Duration;
+/// assert_eq!(duration::format_duration(Duration::milliseconds(90_500)), "1m30s");
+/// ```
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.num_seconds().max(0);
+    let units = [(86400, "d"), (3600, "h"), (60, "m"), (1, "s")];
+    let i = units
+        .iter()
+        .position(|&(size, _)| seconds >= size)
+        .unwrap_or(units.len() - 1);
+    let (size, unit) = units[i];
+    let mut text = format!("{}{}", seconds / size, unit);
+    if let Some(&(next, next_unit)) = units.get(i + 1) {
+        let rest = seconds % size / next;
+        if rest > 0 {
+            text.push_str(&format!("{}{}", rest, next_unit));
+        }
+    }
+    text
+}
@@ -0,0 +1,113 @@
+use std::fmt;
+
+/// Errors returned by this library.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The API answered with an HTTP error status.
+    Http {
+        /// Requested URL
+        url: String,
+        /// HTTP status code
+        status: u16,
+    },
+    /// The API could not be reached, e.g. because of DNS, connection or TLS failures.
+    Transport(String),
+    /// A response or a local file could not be parsed.
+    Parse {
+        /// What was being parsed
+        what: String,
+        /// Why parsing failed
+        message: String,
+    },
+    /// A local file could not be read or written.
+    Io {
+        /// What was being done with the file
+        what: String,
+        /// Underlying error
+        source: std::io::Error,
+    },
+    /// The repeater, station or log data that was looked for does not exist.
+    NotFound(String),
+    /// A remote service reported an error.
+    Api(String),
+    /// The check configuration is invalid.
+    InvalidOptions(String),
+    /// The local clock is too far off the API server clock to trust elapsed times.
+    Clock(String),
+}
+
+/// Result type of this library.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    pub(crate) fn parse(what: impl Into<String>, e: impl fmt::Display) -> Self {
+        Error::Parse {
+            what: what.into(),
+            message: e.to_string(),
+        }
+    }
+
+    pub(crate) fn io(what: impl Into<String>, source: std::io::Error) -> Self {
+        Error::Io {
+            what: what.into(),
+            source,
+        }
+    }
+
+    /// Short name of the kind of error: http, transport, parse, io, not_found, api, config or
+    /// clock.
+    pub fn category(&self) -> &'static str {
+        match self {
+            Error::Http { .. } => "http",
+            Error::Transport(_) => "transport",
+            Error::Parse { .. } => "parse",
+            Error::Io { .. } => "io",
+            Error::NotFound(_) => "not_found",
+            Error::Api(_) => "api",
+            Error::InvalidOptions(_) => "config",
+            Error::Clock(_) => "clock",
+        }
+    }
+
+    /// HTTP status returned by the API, if any.
+    pub fn http_status(&self) -> Option<u16> {
+        match self {
+            Error::Http { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// Whether trying again later may succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Http { status, .. } => *status == 429 || *status >= 500,
+            Error::Transport(_) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Http { url, status } => write!(f, "{}: status code {}", url, status),
+            Error::Transport(message) => f.write_str(message),
+            Error::Parse { what, message } => write!(f, "error parsing {}: {}", what, message),
+            Error::Io { what, source } => write!(f, "error {}: {}", what, source),
+            Error::NotFound(message)
+            | Error::Api(message)
+            | Error::InvalidOptions(message)
+            | Error::Clock(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
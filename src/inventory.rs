@@ -0,0 +1,79 @@
+//! Asset inventory of repeaters, gathering what BrandMeister knows of their hardware,
+//! firmware, frequencies and location for a club's records.
+
+use crate::{BmClient, RepeaterInventory, Result};
+
+/// Columns of the CSV inventory, in the order of the [RepeaterInventory] fields.
+const CSV_HEADER: &str = "id,callsign,hardware,firmware,tx,rx,colorcode,city,lat,lng";
+
+/// Fetch the inventory of each of the `repeaters`, failing if any lookup fails.
+///
+/// Example:
+/// ```no_run
+/// use brandmeister::{inventory, BmClient};
+/// let items = inventory::collect(&BmClient::new(), &[270107, 270108]).unwrap();
+/// print!("{}", inventory::csv(&items));
+/// ```
+pub fn collect(client: &BmClient, repeaters: &[u32]) -> Result<Vec<RepeaterInventory>> {
+    repeaters.iter().map(|id| client.inventory(*id)).collect()
+}
+
+/// Render `items` as CSV with a header line.
+pub fn csv(items: &[RepeaterInventory]) -> String {
+    let mut csv = format!("{}\n", CSV_HEADER);
+    for item in items {
+        let text = |value: &Option<String>| field(value.as_deref().unwrap_or_default());
+        let number = |value: Option<String>| value.unwrap_or_default();
+        let fields = [
+            item.id.to_string(),
+            text(&item.callsign),
+            text(&item.hardware),
+            text(&item.firmware),
+            text(&item.tx),
+            text(&item.rx),
+            number(item.colorcode.map(|c| c.to_string())),
+            text(&item.city),
+            number(item.lat.map(|l| l.to_string())),
+            number(item.lng.map(|l| l.to_string())),
+        ];
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Quote a CSV field if it contains a separator, a quote or a line break.
+fn field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_quotes_separators_quotes_and_line_breaks() {
+        let item = RepeaterInventory {
+            id: 270107,
+            callsign: Some("ON0ABC".to_string()),
+            hardware: Some("MMDVM \"HS\" Hat".to_string()),
+            firmware: None,
+            tx: Some("439.5000".to_string()),
+            rx: Some("431.8375".to_string()),
+            colorcode: Some(1),
+            city: Some("Liège, BE\nnear \"the\" station".to_string()),
+            lat: Some(50.63),
+            lng: None,
+        };
+        assert_eq!(
+            csv(&[item]),
+            "id,callsign,hardware,firmware,tx,rx,colorcode,city,lat,lng\n\
+             270107,ON0ABC,\"MMDVM \"\"HS\"\" Hat\",,439.5000,431.8375,1,\
+             \"Liège, BE\nnear \"\"the\"\" station\",50.63,\n"
+        );
+    }
+}
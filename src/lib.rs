@@ -2,6 +2,20 @@
 //! using [BrandMeister]'s API v2 returns the time elapsed in seconds.
 //! It is not a full client for the brandmeister API.
 //!
+//! The [aprs] module can be used to cross-check the repeater's APRS beacon, and the [mmdvm] module
+//! to compare with the local MMDVMHost log when running on the repeater itself.
+//! A [BmClient] with a [Cache] shares API responses between checks of the same poll cycle,
+//! and a [StateDir] keeps state between checks. A [RetryPolicy] retries transient API failures.
+//!
+//! HTTP requests go through a [Transport]. The default one uses ureq and is enabled by the
+//! `ureq` feature; disable it to provide another HTTP stack, e.g. to build for `wasm32-wasi`.
+//! On Unix, [UnixSocketTransport] talks to a local proxy over a Unix socket.
+//! [network] abstracts the queries the check makes of the DMR network behind a provider trait.
+//!
+//! The [check] module runs the complete check done by the plugin and evaluates its thresholds,
+//! [pushgateway] pushes its results to a Prometheus Pushgateway, and [status_file] gathers them
+//! in a JSON file for web pages.
+//!
 //! See check_brandmeister for a client implementing a [nagios] plugin using this library.
 //!
 //! [BrandMeister]: https://brandmeister.network/
@@ -9,34 +23,82 @@
 
 #![warn(missing_docs)]
 
-use anyhow::{Context, Result};
-use chrono::{NaiveDateTime, TimeZone, Utc};
-use serde::Deserialize;
+pub mod aprs;
+mod cache;
+pub mod check;
+mod client;
+pub mod duration;
+mod error;
+pub mod inventory;
+mod lock;
+pub mod mmdvm;
+pub mod network;
+pub mod output;
+pub mod pushgateway;
+mod retry;
+pub mod snapshot;
+mod state;
+pub mod status_file;
+pub mod thresholds;
+mod timing;
+mod transport;
+#[cfg(unix)]
+mod unix_socket;
 
-#[derive(Debug, Deserialize)]
-struct RepeaterStatus {
-    last_seen: String,
-}
+#[cfg(feature = "ureq")]
+use chrono::{DateTime, Utc};
 
-fn get_bm_repeater_last_update(repeater_id: u32) -> Result<String, anyhow::Error> {
-    let request_url = format!("https://api.brandmeister.network/v2/device/{}", repeater_id);
-    let status: RepeaterStatus = ureq::get(&request_url)
-        .call()?
-        .into_json()
-        .context("error parsing brandmeister API result, ensure repeater id is valid")?;
-    Ok(status.last_seen)
-}
+pub use cache::Cache;
+pub use client::{BmClient, RepeaterInventory, RepeaterMetadata};
+pub use error::{Error, Result};
+pub use retry::{Jitter, RetryPolicy};
+pub use state::StateDir;
+pub use timing::Timings;
+#[cfg(feature = "ureq")]
+pub use transport::UreqTransport;
+pub use transport::{user_agent, HttpResponse, ResolveOverride, Transport};
+#[cfg(unix)]
+pub use unix_socket::UnixSocketTransport;
 
 /// Return the number of seconds since the repeater was seen online on BrandMeister.
 ///
+/// Requires the `ureq` feature, use a [BmClient] with another [Transport] otherwise.
+///
 /// Example:
 /// ```no_run
 /// use brandmeister::last_seen_seconds;
 /// let seconds :i64 = last_seen_seconds(270107).unwrap();
 /// ```
+#[cfg(feature = "ureq")]
 pub fn last_seen_seconds(repeater_id: u32) -> Result<i64> {
-    let last_update_str = get_bm_repeater_last_update(repeater_id)?;
-    let naive_last_update = NaiveDateTime::parse_from_str(&last_update_str, "%Y-%m-%d %H:%M:%S")?;
-    let last_update = Utc.from_utc_datetime(&naive_last_update);
-    Ok(Utc::now().signed_duration_since(last_update).num_seconds())
+    BmClient::new().last_seen_seconds(repeater_id)
+}
+
+/// Return the time elapsed since the repeater was seen online on BrandMeister.
+///
+/// Requires the `ureq` feature, use a [BmClient] with another [Transport] otherwise.
+///
+/// Example:
+/// ```no_run
+/// use brandmeister::last_seen_elapsed;
+/// let elapsed :chrono::Duration = last_seen_elapsed(270107).unwrap();
+/// ```
+#[cfg(feature = "ureq")]
+pub fn last_seen_elapsed(repeater_id: u32) -> Result<chrono::Duration> {
+    BmClient::new().last_seen_elapsed(repeater_id)
+}
+
+/// Return the time the repeater was last seen online on BrandMeister.
+///
+/// Requires the `ureq` feature, use a [BmClient] with another [Transport] otherwise.
+///
+/// Example:
+/// ```no_run
+/// use brandmeister::last_seen_at;
+/// let last_seen = last_seen_at(270107).unwrap();
+/// println!("last seen at {}", last_seen.to_rfc3339());
+/// ```
+#[cfg(feature = "ureq")]
+pub fn last_seen_at(repeater_id: u32) -> Result<DateTime<Utc>> {
+    BmClient::new().last_seen_at(repeater_id)
 }
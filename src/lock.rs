@@ -0,0 +1,71 @@
+//! Advisory lock files serializing plugin invocations that share a cache or state directory.
+//!
+//! A lock is a file created exclusively and removed when released. Locks left by an
+//! invocation killed by the scheduler timeout are broken once well past the wait timeout.
+
+use std::fs::{self, OpenOptions};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::{Error, Result};
+
+/// How long to wait for another invocation holding a lock before going ahead anyway.
+pub(crate) const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A held lock, released when dropped.
+#[derive(Debug)]
+pub(crate) struct FileLock {
+    path: PathBuf,
+}
+
+impl FileLock {
+    /// Acquire the lock at `path`, waiting up to [LOCK_TIMEOUT] for another holder.
+    ///
+    /// Returns `None` on timeout, leaving the caller to decide whether to go ahead unlocked.
+    /// `ready` is called while waiting and stops the wait when it returns true, e.g. when
+    /// the holder already produced what the caller needs.
+    pub(crate) fn acquire(path: &Path, mut ready: impl FnMut() -> bool) -> Result<Option<Self>> {
+        let deadline = Instant::now() + LOCK_TIMEOUT;
+        loop {
+            if ready() {
+                return Ok(None);
+            }
+            match OpenOptions::new().write(true).create_new(true).open(path) {
+                Ok(_) => {
+                    return Ok(Some(FileLock {
+                        path: path.to_path_buf(),
+                    }))
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    if is_abandoned(path) {
+                        let _ = fs::remove_file(path);
+                        continue;
+                    }
+                    if Instant::now() > deadline {
+                        return Ok(None);
+                    }
+                    sleep(LOCK_POLL_INTERVAL);
+                }
+                Err(e) => return Err(Error::io(format!("locking {}", path.display()), e)),
+            }
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Whether the lock at `path` is older than any invocation could legitimately hold it.
+fn is_abandoned(path: &Path) -> bool {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age > LOCK_TIMEOUT * 3)
+}
@@ -0,0 +1,192 @@
+//! Parsing of the local MMDVMHost log to find the last network activity of the repeater.
+//!
+//! This is useful when the check runs on the repeater itself (e.g. a Pi-Star hotspot), to
+//! compare the local view of the BrandMeister link with the one reported by the API.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use chrono::{Duration, NaiveDateTime, TimeZone, Utc};
+
+use crate::{Error, Result};
+
+/// Log messages that show the repeater is exchanging traffic with the master.
+const NETWORK_MARKERS: [&str; 2] = ["received network", "Logged into the master"];
+
+/// List the log files matching `pattern`, newest first.
+///
+/// Only the file name may contain `*` wildcards, so that `/var/log/pi-star/MMDVM-*.log`
+/// works even when the plugin is not started from a shell. MMDVMHost names its logs by date,
+/// so the newest file is the last one in lexical order.
+fn log_files(pattern: &str) -> Result<Vec<PathBuf>> {
+    let path = Path::new(pattern);
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| Error::InvalidOptions(format!("invalid MMDVM log path {}", pattern)))?;
+    if !name.contains('*') {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map_err(|e| Error::io(format!("reading MMDVM log directory {}", dir.display()), e))?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|n| wildcard_match(name, n))
+        })
+        .map(|entry| entry.path())
+        .collect();
+    files.sort();
+    files.reverse();
+    if files.is_empty() {
+        return Err(Error::NotFound(format!(
+            "no MMDVM log file matches {}",
+            pattern
+        )));
+    }
+    Ok(files)
+}
+
+/// Match `name` against `pattern` where `*` matches any sequence of characters.
+///
+/// Example:
+/// ```
+/// use brandmeister::mmdvm::wildcard_match;
+/// assert!(wildcard_match("MMDVM-*.log", "MMDVM-2023-01-03.log"));
+/// ```
+pub fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = parts.collect();
+    let last = parts.pop();
+    for part in parts {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    match last {
+        Some(last) => rest.len() >= last.len() && rest.ends_with(last),
+        None => rest.is_empty(),
+    }
+}
+
+/// Parse the timestamp of a log line like
+/// `M: 2023-01-03 12:34:56.789 DMR Slot 2, received network voice header from ...`.
+fn parse_line(line: &str) -> Option<NaiveDateTime> {
+    if !NETWORK_MARKERS.iter().any(|m| line.contains(m)) {
+        return None;
+    }
+    let timestamp = line.get(3..26)?;
+    NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S%.3f").ok()
+}
+
+fn last_network_activity(file: &Path) -> Result<Option<NaiveDateTime>> {
+    let mut reader = BufReader::new(
+        File::open(file)
+            .map_err(|e| Error::io(format!("opening MMDVM log {}", file.display()), e))?,
+    );
+    let mut last = None;
+    let mut line = Vec::new();
+    // Talker aliases and corrupted writes may put invalid UTF-8 in the log, which must not
+    // hide the activity logged after it.
+    loop {
+        line.clear();
+        let read = reader
+            .read_until(b'\n', &mut line)
+            .map_err(|e| Error::io(format!("reading MMDVM log {}", file.display()), e))?;
+        if read == 0 {
+            break;
+        }
+        if let Some(time) = parse_line(&String::from_utf8_lossy(&line)) {
+            last = Some(time);
+        }
+    }
+    Ok(last)
+}
+
+/// Return the number of seconds since the last network activity found in the MMDVMHost logs
+/// matching `pattern`. MMDVMHost logs in UTC.
+///
+/// Example:
+/// ```no_run
+/// use brandmeister::mmdvm::last_network_activity_seconds;
+/// let seconds :i64 = last_network_activity_seconds("/var/log/pi-star/MMDVM-*.log").unwrap();
+/// ```
+pub fn last_network_activity_seconds(pattern: &str) -> Result<i64> {
+    Ok(last_network_activity_elapsed(pattern)?.num_seconds())
+}
+
+/// Return the time elapsed since the last network activity found in the MMDVMHost logs
+/// matching `pattern`.
+///
+/// Example:
+/// ```no_run
+/// use brandmeister::mmdvm::last_network_activity_elapsed;
+/// let elapsed :chrono::Duration =
+///     last_network_activity_elapsed("/var/log/pi-star/MMDVM-*.log").unwrap();
+/// ```
+pub fn last_network_activity_elapsed(pattern: &str) -> Result<Duration> {
+    for file in log_files(pattern)? {
+        if let Some(naive) = last_network_activity(&file)? {
+            let last_activity = Utc.from_utc_datetime(&naive);
+            return Ok(Utc::now().signed_duration_since(last_activity));
+        }
+    }
+    Err(Error::NotFound(format!(
+        "no network activity found in MMDVM logs {}",
+        pattern
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcard_match_exact() {
+        assert!(wildcard_match("MMDVM.log", "MMDVM.log"));
+        assert!(!wildcard_match("MMDVM.log", "MMDVM.log.1"));
+    }
+
+    #[test]
+    fn wildcard_match_prefix_and_suffix() {
+        assert!(wildcard_match("*.log", "MMDVM-2023-01-03.log"));
+        assert!(wildcard_match("MMDVM-*", "MMDVM-2023-01-03.log"));
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("a*b*c", "abc"));
+    }
+
+    #[test]
+    fn wildcard_match_no_match() {
+        assert!(!wildcard_match("MMDVM-*.log", "DMRGateway-2023-01-03.log"));
+        assert!(!wildcard_match("ab*ba", "aba"));
+    }
+
+    #[test]
+    fn activity_after_invalid_utf8() {
+        let file = std::env::temp_dir().join(format!("check_bm_mmdvm_{}.log", std::process::id()));
+        let log = [
+            &b"M: 2023-01-03 12:00:00.000 DMR Slot 2, received network voice header\n"[..],
+            b"M: 2023-01-03 12:01:00.000 DMR Talker Alias (Data Format 1): \"ON\xff\xfe\"\n",
+            b"M: 2023-01-03 12:02:00.000 DMR Slot 1, received network end of voice\r\n",
+            b"M: 2023-01-03 12:03:00.000 DMR Slot 1, RF voice header",
+        ]
+        .concat();
+        std::fs::write(&file, log).unwrap();
+        let last = last_network_activity(&file).unwrap();
+        std::fs::remove_file(&file).unwrap();
+        let expected = NaiveDateTime::parse_from_str("2023-01-03 12:02:00", "%Y-%m-%d %H:%M:%S");
+        assert_eq!(last, Some(expected.unwrap()));
+    }
+}
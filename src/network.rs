@@ -0,0 +1,68 @@
+//! The DMR networks a repeater can be checked on.
+//!
+//! A [NetworkProvider] answers the questions the check asks of every network: when a device
+//! was last seen and how it is registered. [BmClient] is the BrandMeister provider, and the
+//! only one so far; other networks are meant to be added as providers behind their own cargo
+//! feature, selected with [Network]. The extra checks, e.g. on talkgroups or masters, remain
+//! BrandMeister specific.
+
+use std::fmt::Debug;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+
+use crate::{BmClient, RepeaterMetadata, Result};
+
+/// Queries a DMR network about its devices.
+pub trait NetworkProvider: Debug + Send + Sync {
+    /// Name of the network, as given to `--network`.
+    fn name(&self) -> &'static str;
+
+    /// Time the device was last seen online on the network.
+    fn last_seen_at(&self, device: u32) -> Result<DateTime<Utc>>;
+
+    /// Callsign and city the device is registered with.
+    fn metadata(&self, device: u32) -> Result<RepeaterMetadata>;
+}
+
+impl NetworkProvider for BmClient {
+    fn name(&self) -> &'static str {
+        Network::Brandmeister.name()
+    }
+
+    fn last_seen_at(&self, device: u32) -> Result<DateTime<Utc>> {
+        BmClient::last_seen_at(self, device)
+    }
+
+    fn metadata(&self, device: u32) -> Result<RepeaterMetadata> {
+        BmClient::metadata(self, device)
+    }
+}
+
+/// A network with a [NetworkProvider].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Network {
+    /// The BrandMeister network, `brandmeister`
+    #[default]
+    Brandmeister,
+}
+
+impl Network {
+    /// Name of the network on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Network::Brandmeister => "brandmeister",
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [Network::Brandmeister]
+            .into_iter()
+            .find(|n| n.name() == s)
+            .ok_or_else(|| format!("invalid network {}", s))
+    }
+}
@@ -0,0 +1,356 @@
+//! Encoding of text for nagios plugin output.
+//!
+//! Nagios uses `|` to separate the status text from the perfdata and newlines to separate
+//! long output, so strings coming from the API or local files must not contain them.
+//!
+//! The [OutputFormatter] trait renders check outcomes for nagios or other monitoring systems.
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use chrono::SecondsFormat;
+use nagiosplugin::{CheckResult, Resource, ServiceState, ToPerfString, Unit, UnitString};
+
+use crate::check::{CheckOutcome, NagiosState};
+
+/// Make `text` safe to include in a nagios status line.
+///
+/// Pipes are replaced by `/`, line breaks and tabs by spaces, and other control characters
+/// are removed.
+///
+/// Example:
+/// ```
+/// use brandmeister::output::sanitize_text;
+/// assert_eq!(sanitize_text("ON0ABC | Liège\n"), "ON0ABC / Liège ");
+/// ```
+pub fn sanitize_text(text: &str) -> String {
+    text.chars()
+        .filter_map(|c| match c {
+            '|' => Some('/'),
+            '\n' | '\r' | '\t' => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect()
+}
+
+/// Make `label` safe to use as a quoted perfdata label.
+///
+/// Quotes and equal signs are removed on top of the [sanitize_text] rules.
+pub fn sanitize_label(label: &str) -> String {
+    sanitize_text(label).replace(['\'', '='], "")
+}
+
+/// Format a perfdata value with at most one decimal, e.g. `152` or `2.5`.
+///
+/// Example:
+/// ```
+/// use brandmeister::output::format_number;
+/// assert_eq!(format_number(152.0), "152");
+/// assert_eq!(format_number(119.0 / 60.0), "2.0");
+/// assert_eq!(format_number(1.25), "1.2");
+/// ```
+pub fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{}", value)
+    } else {
+        format!("{:.1}", value)
+    }
+}
+
+/// A perfdata value as nagiosplugin renders it.
+struct PerfValue(String);
+
+impl ToPerfString for PerfValue {
+    fn to_perf_string(&self) -> String {
+        self.0.clone()
+    }
+}
+
+/// Renders check outcomes in the format of a monitoring system.
+///
+/// The plugin provides [Nagios], [Json], [Checkmk] and [Prometheus], looked up by name with
+/// [formatter]. Implement this trait to feed another system without post-processing the
+/// output.
+///
+/// Example:
+/// ```
+/// use brandmeister::check::CheckOutcome;
+/// use brandmeister::output::OutputFormatter;
+///
+/// #[derive(Debug)]
+/// struct StateOnly;
+///
+/// impl OutputFormatter for StateOnly {
+///     fn name(&self) -> &str {
+///         "state"
+///     }
+///
+///     fn format(&self, outcome: &CheckOutcome) -> String {
+///         outcome.state.to_string()
+///     }
+/// }
+/// ```
+pub trait OutputFormatter: Debug + Send + Sync {
+    /// Name of the format, as given to `--output`.
+    fn name(&self) -> &str;
+
+    /// Render `outcome`, without a trailing line break.
+    fn format(&self, outcome: &CheckOutcome) -> String;
+}
+
+/// Names of the formats provided by the plugin.
+pub const FORMATS: &[&str] = &["nagios", "json", "checkmk", "prometheus"];
+
+/// The formatter provided by the plugin for the format named `name`, see [FORMATS].
+pub fn formatter(name: &str) -> Option<Arc<dyn OutputFormatter>> {
+    match name {
+        "nagios" => Some(Arc::new(Nagios)),
+        "json" => Some(Arc::new(Json)),
+        "checkmk" => Some(Arc::new(Checkmk)),
+        "prometheus" => Some(Arc::new(Prometheus)),
+        _ => None,
+    }
+}
+
+/// Nagios status line with perfdata, and the messages as long output.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Nagios;
+
+impl OutputFormatter for Nagios {
+    fn name(&self) -> &str {
+        "nagios"
+    }
+
+    fn format(&self, outcome: &CheckOutcome) -> String {
+        if let Some(error) = &outcome.error {
+            return format!("{}: {}", outcome.state, sanitize_text(&error.message));
+        }
+        let mut resource = Resource::new(sanitize_text(&outcome.name))
+            .with_description(sanitize_text(&outcome.description))
+            .with_fixed_state(service_state(outcome.state));
+        if let Some(last_seen) = outcome.last_seen {
+            resource.push_result(CheckResult::new().with_message(format!(
+                "last seen at {}",
+                last_seen.to_rfc3339_opts(SecondsFormat::Secs, true)
+            )));
+        }
+        for message in &outcome.messages {
+            resource.push_result(CheckResult::new().with_message(sanitize_text(message)));
+        }
+        for p in &outcome.perfdata {
+            let unit = match p.unit.as_str() {
+                "s" => Unit::Seconds,
+                "ms" => Unit::Milliseconds,
+                "%" => Unit::Percentage,
+                "" => Unit::None,
+                other => Unit::Other(UnitString::new_unchecked(sanitize_label(other))),
+            };
+            let number = |v: f64| PerfValue(format_number(v));
+            let threshold = |t: f64| PerfValue(p.threshold_range(t));
+            let mut perf = nagiosplugin::PerfData::new(sanitize_label(&p.name), number(p.value))
+                .with_thresholds(p.warning.map(threshold), p.critical.map(threshold))
+                .with_unit(unit);
+            if let Some(minimum) = p.minimum {
+                perf = perf.with_minimum(number(minimum));
+            }
+            resource.push_result(CheckResult::new().with_perf_data(perf));
+        }
+        resource.nagios_result().1
+    }
+}
+
+fn service_state(state: NagiosState) -> ServiceState {
+    match state {
+        NagiosState::Ok => ServiceState::Ok,
+        NagiosState::Warning => ServiceState::Warning,
+        NagiosState::Critical => ServiceState::Critical,
+        NagiosState::Unknown => ServiceState::Unknown,
+    }
+}
+
+/// The [CheckOutcome] as a JSON object, with a structured error object when the check fails.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json;
+
+impl OutputFormatter for Json {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn format(&self, outcome: &CheckOutcome) -> String {
+        serde_json::to_string(outcome).expect("outcome serializes to JSON")
+    }
+}
+
+/// Checkmk [local check] line, `<state> "<service>" <metrics> <summary>`.
+///
+/// [local check]: https://docs.checkmk.com/latest/en/localchecks.html
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Checkmk;
+
+impl OutputFormatter for Checkmk {
+    fn name(&self) -> &str {
+        "checkmk"
+    }
+
+    fn format(&self, outcome: &CheckOutcome) -> String {
+        let metrics: Vec<String> = outcome
+            .perfdata
+            .iter()
+            .map(|p| {
+                // Checkmk metric thresholds are upper levels, the state of low-is-bad metrics
+                // is in the state column.
+                let threshold = |t: Option<f64>| {
+                    t.filter(|_| !p.low_is_bad)
+                        .map(format_number)
+                        .unwrap_or_default()
+                };
+                format!(
+                    "{}={};{};{}",
+                    sanitize_label(&p.name).replace([' ', '|'], "_"),
+                    format_number(p.value),
+                    threshold(p.warning),
+                    threshold(p.critical)
+                )
+            })
+            .collect();
+        let metrics = match metrics.is_empty() {
+            true => "-".to_string(),
+            false => metrics.join("|"),
+        };
+        let mut summary = match &outcome.error {
+            Some(error) => error.message.clone(),
+            None => outcome.description.clone(),
+        };
+        for message in &outcome.messages {
+            summary = format!("{}, {}", summary, message);
+        }
+        format!(
+            "{} \"{}\" {} {}",
+            outcome.state.exit_code(),
+            sanitize_text(&outcome.name).replace('"', ""),
+            metrics,
+            sanitize_text(&summary)
+        )
+    }
+}
+
+/// Prometheus text exposition format: a `brandmeister_<name>` gauge per perfdata metric,
+/// along with `brandmeister_state` (the nagios exit code) and `brandmeister_check_success`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Prometheus;
+
+impl OutputFormatter for Prometheus {
+    fn name(&self) -> &str {
+        "prometheus"
+    }
+
+    fn format(&self, outcome: &CheckOutcome) -> String {
+        let lines: Vec<String> = gauges(outcome)
+            .iter()
+            .map(|(name, value)| {
+                let name = metric_name(name);
+                format!("# TYPE {} gauge\n{} {}", name, name, value)
+            })
+            .collect();
+        lines.join("\n")
+    }
+}
+
+impl Prometheus {
+    /// Render the outcomes of several repeaters as a single exposition, each sample with a
+    /// `repeater` label so that the series of the repeaters can be aggregated.
+    pub fn format_repeaters(&self, outcomes: &[(String, CheckOutcome)]) -> String {
+        let mut families: Vec<(String, Vec<String>)> = Vec::new();
+        for (repeater, outcome) in outcomes {
+            for (name, value) in gauges(outcome) {
+                let name = metric_name(&name);
+                let sample = format!(
+                    "{}{{repeater=\"{}\"}} {}",
+                    name,
+                    label_value(repeater),
+                    value
+                );
+                match families.iter_mut().find(|(family, _)| *family == name) {
+                    Some((_, samples)) => samples.push(sample),
+                    None => families.push((name, vec![sample])),
+                }
+            }
+        }
+        let lines: Vec<String> = families
+            .iter()
+            .map(|(name, samples)| format!("# TYPE {} gauge\n{}", name, samples.join("\n")))
+            .collect();
+        lines.join("\n")
+    }
+}
+
+/// The state, success and perfdata of `outcome`, as gauge names and values.
+fn gauges(outcome: &CheckOutcome) -> Vec<(String, f64)> {
+    let mut gauges = vec![
+        ("state".to_string(), f64::from(outcome.state.exit_code())),
+        (
+            "check_success".to_string(),
+            f64::from(u8::from(outcome.error.is_none())),
+        ),
+    ];
+    gauges.extend(outcome.perfdata.iter().map(|p| (p.name.clone(), p.value)));
+    gauges
+}
+
+/// Escape `value` for a Prometheus label value.
+fn label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Make `name` a valid Prometheus metric name.
+pub(crate) fn metric_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("brandmeister_{}", name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::check::PerfData;
+    use crate::Error;
+
+    fn outcome(seconds: i64) -> CheckOutcome {
+        let mut outcome = CheckOutcome::failed("test".to_string(), &Error::Api("x".to_string()));
+        outcome.state = NagiosState::Ok;
+        outcome.error = None;
+        outcome.perfdata = vec![PerfData::seconds("last_seen", seconds, 600, 900)];
+        outcome
+    }
+
+    #[test]
+    fn prometheus_repeaters_share_metric_families() {
+        let outcomes = vec![
+            ("270107".to_string(), outcome(42)),
+            ("270108".to_string(), outcome(7)),
+        ];
+        let exposition = Prometheus.format_repeaters(&outcomes);
+        assert_eq!(
+            exposition
+                .matches("# TYPE brandmeister_last_seen gauge")
+                .count(),
+            1
+        );
+        assert!(exposition.contains(
+            "brandmeister_last_seen{repeater=\"270107\"} 42\nbrandmeister_last_seen{repeater=\"270108\"} 7"
+        ));
+        assert!(!exposition.contains("270107_"));
+    }
+
+    #[test]
+    fn prometheus_label_values_are_escaped() {
+        assert_eq!(label_value("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
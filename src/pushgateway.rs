@@ -0,0 +1,53 @@
+//! Push of check results to a [Prometheus Pushgateway], for cron-driven setups without a
+//! scrapable exporter.
+//!
+//! Every perfdata metric is pushed as a `brandmeister_<name>` gauge, along with
+//! `brandmeister_state` (the nagios exit code) and `brandmeister_check_success`, grouped by
+//! job and repeater so that each repeater replaces only its own metrics.
+//!
+//! [Prometheus Pushgateway]: https://github.com/prometheus/pushgateway
+
+use crate::check::CheckOutcome;
+use crate::output::{OutputFormatter, Prometheus};
+use crate::transport::{encode_query, Transport};
+use crate::{Error, Result};
+
+/// Job name used in the grouping key.
+pub(crate) const JOB: &str = "check_brandmeister";
+
+/// Push the metrics of `outcome` for `repeater` to the Pushgateway at `url`, performing the
+/// request with `transport`.
+///
+/// Example:
+/// ```no_run
+/// use brandmeister::check::{check, CheckOptions};
+/// use brandmeister::{pushgateway, UreqTransport};
+/// let outcome = check(&CheckOptions::builder().repeater(270107).build().unwrap());
+/// pushgateway::push(&UreqTransport::default(), "http://localhost:9091", 270107, &outcome)
+///     .unwrap();
+/// ```
+pub fn push(
+    transport: &dyn Transport,
+    url: &str,
+    repeater: u32,
+    outcome: &CheckOutcome,
+) -> Result<()> {
+    let push_url = format!(
+        "{}/metrics/job/{}/repeater/{}",
+        url.trim_end_matches('/'),
+        encode_query(JOB),
+        repeater
+    );
+    let response = transport.put(
+        &push_url,
+        &[("Content-Type", "text/plain; version=0.0.4")],
+        &(Prometheus.format(outcome) + "\n"),
+    )?;
+    if response.status >= 400 {
+        return Err(Error::Http {
+            url: push_url,
+            status: response.status,
+        });
+    }
+    Ok(())
+}
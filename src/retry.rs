@@ -0,0 +1,255 @@
+//! Retrying of API requests that failed with a transient error.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use chrono::Duration;
+
+use crate::{duration, Result};
+
+/// How much of the backoff delay is randomized, to avoid retrying in lockstep with other
+/// checks hitting the same outage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Jitter {
+    /// Wait exactly the backoff delay
+    None,
+    /// Wait a random time between zero and the backoff delay
+    Full,
+    /// Wait half the backoff delay plus a random time up to the other half
+    Equal,
+}
+
+impl FromStr for Jitter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Jitter::None),
+            "full" => Ok(Jitter::Full),
+            "equal" => Ok(Jitter::Equal),
+            _ => Err(format!(
+                "invalid jitter {}, expected none, full or equal",
+                s
+            )),
+        }
+    }
+}
+
+/// Exponential backoff policy applied to requests failing with a retryable error, see
+/// [crate::Error::is_retryable].
+///
+/// The default policy does not retry, so that active checks fail fast and let nagios
+/// schedule the next attempt.
+///
+/// Example:
+/// ```
+/// use brandmeister::RetryPolicy;
+/// let policy: RetryPolicy = "count=3,base=500ms,max=5s,jitter=full".parse().unwrap();
+/// assert_eq!(policy.count(), 3);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    count: u32,
+    base: Duration,
+    max: Duration,
+    jitter: Jitter,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            count: 0,
+            base: Duration::milliseconds(500),
+            max: Duration::seconds(5),
+            jitter: Jitter::Full,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Retry up to `count` times, with the default backoff of 500ms doubling up to 5s.
+    pub fn new(count: u32) -> Self {
+        RetryPolicy {
+            count,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// Delay before the first retry, doubled for each following one.
+    pub fn with_base(mut self, base: Duration) -> Self {
+        self.base = base;
+        self
+    }
+
+    /// Longest delay between two attempts.
+    pub fn with_max(mut self, max: Duration) -> Self {
+        self.max = max;
+        self
+    }
+
+    /// Randomization of the delays.
+    pub fn with_jitter(mut self, jitter: Jitter) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Maximum number of retries after the first attempt.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Delay before retry number `retry`, starting at 0.
+    fn delay(&self, retry: u32) -> std::time::Duration {
+        let base = self.base.num_milliseconds().max(0) as u64;
+        let max = self.max.num_milliseconds().max(0) as u64;
+        let backoff = base.saturating_mul(1 << retry.min(32)).min(max);
+        let millis = match self.jitter {
+            Jitter::None => backoff,
+            Jitter::Full => random_below(backoff + 1),
+            Jitter::Equal => backoff / 2 + random_below(backoff / 2 + 1),
+        };
+        std::time::Duration::from_millis(millis)
+    }
+
+    /// Run `attempt` until it succeeds, fails with an error that is not retryable, or the
+    /// retries are exhausted.
+    pub(crate) fn run<T>(&self, mut attempt: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut retry = 0;
+        loop {
+            match attempt() {
+                Err(e) if e.is_retryable() && retry < self.count => {
+                    std::thread::sleep(self.delay(retry));
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// A number below `bound`, random enough to spread retries without depending on a RNG crate.
+fn random_below(bound: u64) -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() as u64)
+        .unwrap_or_default();
+    // Mix in a stack address, which differs between processes with address space layout
+    // randomization, as the process id is not available on wasi.
+    let local = 0u8;
+    let address = &local as *const u8 as u64;
+    let mut x = nanos ^ (address << 16) ^ 0x9e37_79b9_7f4a_7c15;
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    x % bound.max(1)
+}
+
+impl FromStr for RetryPolicy {
+    type Err = String;
+
+    /// Parse comma separated `key=value` settings, e.g. `count=3,base=500ms,max=5s,jitter=full`.
+    /// Omitted settings keep their default value.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut policy = RetryPolicy::default();
+        for setting in s.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (key, value) = setting
+                .split_once('=')
+                .ok_or_else(|| format!("invalid retry setting {}, expected key=value", setting))?;
+            let value = value.trim();
+            let millis = || duration::parse(value, Duration::milliseconds(1));
+            match key.trim() {
+                "count" => {
+                    policy.count = value
+                        .parse()
+                        .map_err(|_| format!("invalid retry count {}", value))?
+                }
+                "base" => policy.base = millis()?,
+                "max" => policy.max = millis()?,
+                "jitter" => policy.jitter = value.parse()?,
+                other => return Err(format!("unknown retry setting {}", other)),
+            }
+        }
+        if policy.base < Duration::zero() || policy.max < policy.base {
+            return Err("retry delays must be positive, with max at least base".to_string());
+        }
+        Ok(policy)
+    }
+}
+
+impl fmt::Display for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let jitter = match self.jitter {
+            Jitter::None => "none",
+            Jitter::Full => "full",
+            Jitter::Equal => "equal",
+        };
+        write!(
+            f,
+            "count={},base={}ms,max={}ms,jitter={}",
+            self.count,
+            self.base.num_milliseconds(),
+            self.max.num_milliseconds(),
+            jitter
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_policies() {
+        assert_eq!("".parse(), Ok(RetryPolicy::default()));
+        assert_eq!("count=2".parse(), Ok(RetryPolicy::new(2)));
+        let policy = RetryPolicy::new(3)
+            .with_base(Duration::milliseconds(250))
+            .with_max(Duration::seconds(2))
+            .with_jitter(Jitter::None);
+        for s in [
+            "count=3,base=250ms,max=2s,jitter=none",
+            " jitter = none , max=2000, base=250, count=3,",
+        ] {
+            assert_eq!(s.parse(), Ok(policy), "{:?}", s);
+        }
+        assert_eq!(policy.to_string().parse(), Ok(policy));
+        assert_eq!(
+            "base=0,max=0".parse::<RetryPolicy>().map(|p| p.delay(3)),
+            Ok(Default::default())
+        );
+    }
+
+    #[test]
+    fn invalid_policies() {
+        for (s, expected) in [
+            ("count", "invalid retry setting count, expected key=value"),
+            ("count=-1", "invalid retry count -1"),
+            ("count=three", "invalid retry count three"),
+            (
+                "base=1x",
+                "invalid duration 1x, expected e.g. 90s, 10m or 2h30m",
+            ),
+            (
+                "max=100ms",
+                "retry delays must be positive, with max at least base",
+            ),
+            (
+                "base=10s",
+                "retry delays must be positive, with max at least base",
+            ),
+            (
+                "jitter=some",
+                "invalid jitter some, expected none, full or equal",
+            ),
+            ("tries=3", "unknown retry setting tries"),
+        ] {
+            assert_eq!(
+                s.parse::<RetryPolicy>(),
+                Err(expected.to_string()),
+                "{:?}",
+                s
+            );
+        }
+    }
+}
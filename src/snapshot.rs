@@ -0,0 +1,173 @@
+//! Baseline snapshots of the full API records of repeaters, compared later to detect any
+//! change of their registration, beyond the fields that specific checks look at.
+//!
+//! A snapshot file holds the time it was taken and the records keyed by repeater id:
+//! ```text
+//! {"taken_at":"2026-10-14T04:28:58Z","repeaters":{"270107":{"callsign":"ON0ABC",...}}}
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::state::write_atomic;
+use crate::{BmClient, Error, Result};
+
+/// Fields that change in normal operation, left out of snapshots.
+const VOLATILE_FIELDS: &[&str] = &["last_seen", "last_updated", "status", "lastKnownMaster"];
+
+/// The API records of a set of repeaters at a point in time.
+///
+/// Example:
+/// ```no_run
+/// use brandmeister::snapshot::Snapshot;
+/// use brandmeister::BmClient;
+/// let client = BmClient::new();
+/// let baseline = Snapshot::load("/var/lib/check_brandmeister/baseline.json").unwrap();
+/// let current = Snapshot::take(&client, &baseline.repeaters()).unwrap();
+/// for change in baseline.diff(&current) {
+///     println!("{}", change);
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// When the records were fetched
+    pub taken_at: DateTime<Utc>,
+    #[serde(rename = "repeaters")]
+    records: BTreeMap<u32, Map<String, Value>>,
+}
+
+impl Snapshot {
+    /// Fetch the records of `repeaters`, failing if any lookup fails.
+    pub fn take(client: &BmClient, repeaters: &[u32]) -> Result<Self> {
+        let mut records = BTreeMap::new();
+        for &id in repeaters {
+            let mut record = client.device_record(id)?;
+            for field in VOLATILE_FIELDS {
+                record.remove(*field);
+            }
+            records.insert(id, record);
+        }
+        Ok(Snapshot {
+            taken_at: Utc::now(),
+            records,
+        })
+    }
+
+    /// Load the snapshot saved at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let body = fs::read_to_string(path)
+            .map_err(|e| Error::io(format!("reading {}", path.display()), e))?;
+        serde_json::from_str(&body)
+            .map_err(|e| Error::parse(format!("snapshot {}", path.display()), e))
+    }
+
+    /// Save the snapshot at `path`, replacing it atomically.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let body = serde_json::to_string(self).map_err(|e| Error::parse("snapshot", e))?;
+        write_atomic(path.as_ref(), &body)
+    }
+
+    /// Ids of the repeaters in the snapshot, in ascending order.
+    pub fn repeaters(&self) -> Vec<u32> {
+        self.records.keys().copied().collect()
+    }
+
+    /// Fields that differ in `later`, for each repeater of this snapshot. A repeater missing
+    /// from `later` has all its fields removed, repeaters only in `later` are left out.
+    pub fn diff(&self, later: &Snapshot) -> Vec<Change> {
+        let empty = Map::new();
+        let mut changes = Vec::new();
+        for (&repeater, before) in &self.records {
+            let after = later.records.get(&repeater).unwrap_or(&empty);
+            let mut fields: Vec<&String> = before.keys().chain(after.keys()).collect();
+            fields.sort_unstable();
+            fields.dedup();
+            for field in fields {
+                let (before, after) = (before.get(field), after.get(field));
+                if before != after {
+                    changes.push(Change {
+                        repeater,
+                        field: field.clone(),
+                        before: before.cloned(),
+                        after: after.cloned(),
+                    });
+                }
+            }
+        }
+        changes
+    }
+}
+
+/// A field of a repeater record that differs between two snapshots.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    /// Id of the repeater
+    pub repeater: u32,
+    /// Name of the field in the API record
+    pub field: String,
+    /// Value in the baseline, if the field was present
+    pub before: Option<Value>,
+    /// Value in the later snapshot, if the field is present
+    pub after: Option<Value>,
+}
+
+impl fmt::Display for Change {
+    /// Format as e.g. `270107 tx: "439.4375" -> "439.5"`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = |v: &Option<Value>| v.as_ref().map_or("(none)".to_string(), Value::to_string);
+        write!(
+            f,
+            "{} {}: {} -> {}",
+            self.repeater,
+            self.field,
+            value(&self.before),
+            value(&self.after)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn snapshot(repeaters: Value) -> Snapshot {
+        let snapshot = json!({"taken_at": "2026-10-14T04:28:58Z", "repeaters": repeaters});
+        serde_json::from_value(snapshot).unwrap()
+    }
+
+    #[test]
+    fn diff_of_changed_added_and_removed_fields() {
+        let baseline = snapshot(json!({
+            "270107": {"callsign": "ON0ABC", "tx": "439.4375", "pep": 10, "city": "Liège"},
+            "270108": {"callsign": "ON0XYZ"},
+        }));
+        let later = snapshot(json!({
+            "270107": {"callsign": "ON0ABC", "tx": "439.5", "colorcode": 1, "city": "Liège"},
+            "270109": {"callsign": "ON0NEW"},
+        }));
+        let changes: Vec<String> = baseline
+            .diff(&later)
+            .iter()
+            .map(Change::to_string)
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                "270107 colorcode: (none) -> 1",
+                "270107 pep: 10 -> (none)",
+                r#"270107 tx: "439.4375" -> "439.5""#,
+                r#"270108 callsign: "ON0XYZ" -> (none)"#,
+            ]
+        );
+        assert!(baseline.diff(&baseline).is_empty());
+    }
+}
@@ -0,0 +1,113 @@
+//! Small JSON state files persisted between plugin invocations.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::lock::FileLock;
+use crate::{Error, Result};
+
+/// A directory of JSON state files, one per key.
+///
+/// Example:
+/// ```no_run
+/// use brandmeister::StateDir;
+/// let state = StateDir::new("/var/lib/check_brandmeister");
+/// let breaches: u32 = state.load("check-270107").unwrap();
+/// state.save("check-270107", &(breaches + 1)).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct StateDir {
+    dir: PathBuf,
+}
+
+impl StateDir {
+    /// Keep state files in `dir`, created when first saving.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        StateDir { dir: dir.into() }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    /// Load the state saved under `key`.
+    ///
+    /// A missing or unreadable state file yields the default state, so that a corrupted
+    /// file does not keep the check UNKNOWN until someone removes it.
+    pub fn load<T: DeserializeOwned + Default>(&self, key: &str) -> Result<T> {
+        let path = self.path(key);
+        match fs::read_to_string(&path) {
+            Ok(body) => Ok(serde_json::from_str(&body).unwrap_or_default()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(T::default()),
+            Err(e) => Err(Error::io(format!("reading {}", path.display()), e)),
+        }
+    }
+
+    /// Save `state` under `key`.
+    pub fn save<T: Serialize>(&self, key: &str, state: &T) -> Result<()> {
+        self.create_dir()?;
+        let body = serde_json::to_string(state).map_err(|e| Error::parse("state", e))?;
+        write_atomic(&self.path(key), &body)
+    }
+
+    /// Load the state saved under `key`, let `update` modify it and save it.
+    ///
+    /// A lock file serializes concurrent updates of the same key, so that simultaneous
+    /// invocations do not overwrite each other's changes. If the lock cannot be acquired in
+    /// time the update goes ahead anyway rather than failing the check.
+    pub fn update<T, R>(&self, key: &str, update: impl FnOnce(&mut T) -> R) -> Result<R>
+    where
+        T: Serialize + DeserializeOwned + Default,
+    {
+        self.create_dir()?;
+        let _lock = FileLock::acquire(&self.dir.join(format!("{}.lock", key)), || false)?;
+        let mut state = self.load(key)?;
+        let result = update(&mut state);
+        self.save(key, &state)?;
+        Ok(result)
+    }
+
+    fn create_dir(&self) -> Result<()> {
+        fs::create_dir_all(&self.dir).map_err(|e| {
+            Error::io(
+                format!("creating state directory {}", self.dir.display()),
+                e,
+            )
+        })
+    }
+}
+
+/// Write to a temporary file first, so readers never see a partial file.
+///
+/// The temporary file name is unique to the process and call, so that concurrent writers
+/// of the same file do not write into each other's temporary file.
+pub(crate) fn write_atomic(path: &Path, body: &str) -> Result<()> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    // The process id is not available on wasi, the clock makes names unique across processes.
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let tmp = path.with_extension(format!(
+        "{}.{}.tmp",
+        nanos,
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::write(&tmp, body).map_err(|e| Error::io(format!("writing {}", tmp.display()), e))?;
+    fs::rename(&tmp, path).map_err(|e| Error::io(format!("writing {}", path.display()), e))
+}
+
+/// A new empty directory named after `name` for the unit tests.
+#[cfg(test)]
+pub(crate) fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("check_bm_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,280 @@
+//! JSON status file gathering the latest outcome of each repeater, for web pages that show a
+//! live status widget by fetching a static file.
+//!
+//! The file holds the time of the last update and the outcomes keyed by repeater id:
+//! ```text
+//! {"updated_at":"2026-10-14T04:28:58Z","repeaters":{"270107":{"state":"OK",...}}}
+//! ```
+//! Each check replaces only the entry of its repeater, under a lock file, and the file is
+//! written atomically so that the web server never serves a partial file. [html] renders
+//! it as a status page for clubs without a monitoring system, [grafana_dashboard] as a
+//! dashboard of the metrics pushed to a [Pushgateway](crate::pushgateway), and [repeaters]
+//! lists the repeaters it covers, e.g. to build their [inventory](crate::inventory).
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Map, Value};
+
+use crate::check::CheckOutcome;
+use crate::duration;
+use crate::lock::FileLock;
+use crate::output::metric_name;
+use crate::pushgateway::JOB;
+use crate::state::write_atomic;
+use crate::{Error, Result};
+
+/// Load the status file at `path`.
+fn load(path: &Path) -> Result<Map<String, Value>> {
+    match fs::read_to_string(path) {
+        Ok(body) => Ok(serde_json::from_str(&body).unwrap_or_default()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Map::new()),
+        Err(e) => Err(Error::io(format!("reading {}", path.display()), e)),
+    }
+}
+
+/// Load the status file at `path`, which must exist.
+fn load_existing(path: &Path) -> Result<Map<String, Value>> {
+    if !path.exists() {
+        return Err(Error::NotFound(format!(
+            "no status file at {}",
+            path.display()
+        )));
+    }
+    load(path)
+}
+
+/// Record `outcome` as the status of `repeater` in the status file at `path`.
+///
+/// Example:
+/// ```no_run
+/// use brandmeister::check::{check, CheckOptions};
+/// use brandmeister::status_file;
+/// let outcome = check(&CheckOptions::builder().repeater(270107).build().unwrap());
+/// status_file::record("/var/www/html/bm-status.json", 270107, &outcome).unwrap();
+/// ```
+pub fn record(path: impl AsRef<Path>, repeater: u32, outcome: &CheckOutcome) -> Result<()> {
+    let path = path.as_ref();
+    let _lock = FileLock::acquire(&path.with_extension("lock"), || false)?;
+    // An unreadable file is replaced rather than blocking updates until someone removes it.
+    let mut status = load(path)?;
+    let now = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+    let mut entry = serde_json::to_value(outcome).map_err(|e| Error::parse("check outcome", e))?;
+    entry["checked_at"] = Value::String(now.clone());
+
+    if !status.get("repeaters").is_some_and(Value::is_object) {
+        status.insert("repeaters".to_string(), Value::Object(Map::new()));
+    }
+    status["repeaters"][repeater.to_string()] = entry;
+    status.insert("updated_at".to_string(), Value::String(now));
+    let body = serde_json::to_string(&status).map_err(|e| Error::parse("status file", e))?;
+    write_atomic(path, &body)
+}
+
+/// Render the status file at `path` as a standalone HTML page with a colour-coded table of
+/// the repeaters and the time since each was last seen.
+pub fn html(path: impl AsRef<Path>) -> Result<String> {
+    let status = load_existing(path.as_ref())?;
+    let now = Utc::now();
+    let mut rows = String::new();
+    let repeaters = status.get("repeaters").and_then(Value::as_object);
+    for entry in repeaters.into_iter().flat_map(|r| r.values()) {
+        let text = |key: &str| entry.get(key).and_then(Value::as_str).unwrap_or_default();
+        let state = text("state");
+        let age = |key: &str| {
+            text(key)
+                .parse::<DateTime<Utc>>()
+                .map(|at| format!("{} ago", duration::format_duration(now - at)))
+                .unwrap_or_else(|_| "unknown".to_string())
+        };
+        rows.push_str(&format!(
+            "<tr><td>{}</td><td class=\"{}\">{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            escape(text("name")),
+            escape(&state.to_lowercase()),
+            escape(state),
+            age("last_seen"),
+            escape(text("description")),
+            age("checked_at"),
+        ));
+    }
+    Ok(format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>BrandMeister repeater status</title>
+<style>
+body {{ font-family: sans-serif; }}
+table {{ border-collapse: collapse; }}
+th, td {{ border: 1px solid #ccc; padding: 4px 8px; text-align: left; }}
+.ok {{ background: #c8e6c9; }}
+.warning {{ background: #fff59d; }}
+.critical {{ background: #ef9a9a; }}
+.unknown {{ background: #e0e0e0; }}
+</style>
+</head>
+<body>
+<h1>BrandMeister repeater status</h1>
+<table>
+<tr><th>Repeater</th><th>State</th><th>Last seen</th><th>Status</th><th>Checked</th></tr>
+{}</table>
+<p>Generated at {}</p>
+</body>
+</html>
+"#,
+        rows,
+        now.to_rfc3339_opts(SecondsFormat::Secs, true)
+    ))
+}
+
+/// Render a Grafana dashboard JSON for the repeaters of the status file at `path`, graphing
+/// the metrics their checks push to a Pushgateway scraped by Prometheus.
+///
+/// The dashboard has a state panel and a graph per metric recorded in the status file, with
+/// a `repeater` variable listing the repeaters and a `datasource` variable to pick the
+/// Prometheus data source on import.
+pub fn grafana_dashboard(path: impl AsRef<Path>) -> Result<String> {
+    let status = load_existing(path.as_ref())?;
+    let mut metrics: Vec<(String, String)> = Vec::new();
+    let entries = status.get("repeaters").and_then(Value::as_object);
+    let perfdata = entries
+        .into_iter()
+        .flat_map(|r| r.values())
+        .filter_map(|entry| entry.get("perfdata").and_then(Value::as_array))
+        .flatten();
+    for p in perfdata {
+        let text = |key: &str| p.get(key).and_then(Value::as_str).unwrap_or_default();
+        if !text("name").is_empty() && !metrics.iter().any(|(name, _)| name == text("name")) {
+            metrics.push((text("name").to_string(), text("unit").to_string()));
+        }
+    }
+    let repeaters: Vec<String> = repeaters(path)?.iter().map(u32::to_string).collect();
+
+    let datasource = json!({ "type": "prometheus", "uid": "${datasource}" });
+    let target = |metric: &str| {
+        json!([{
+            "datasource": datasource,
+            "expr": format!(
+                "{}{{job=\"{}\", repeater=~\"$repeater\"}}",
+                metric_name(metric),
+                JOB
+            ),
+            "legendFormat": "{{repeater}}",
+            "refId": "A",
+        }])
+    };
+    let mut states = Map::new();
+    let names = [
+        (0, "OK", "green"),
+        (1, "WARNING", "yellow"),
+        (2, "CRITICAL", "red"),
+        (3, "UNKNOWN", "gray"),
+    ];
+    for (value, text, color) in names {
+        let mapping = json!({ "text": text, "color": color, "index": value });
+        states.insert(value.to_string(), mapping);
+    }
+    let mut panels = vec![json!({
+        "id": 1,
+        "type": "stat",
+        "title": "State",
+        "datasource": datasource,
+        "gridPos": { "x": 0, "y": 0, "w": 24, "h": 4 },
+        "targets": target("state"),
+        "options": { "colorMode": "background", "textMode": "value_and_name" },
+        "fieldConfig": {
+            "defaults": {
+                "mappings": [{ "type": "value", "options": states }],
+            },
+        },
+    })];
+    for (i, (name, unit)) in metrics.iter().enumerate() {
+        let unit = match unit.as_str() {
+            "s" => "s",
+            "ms" => "ms",
+            "%" => "percent",
+            "m" => "lengthm",
+            _ => "none",
+        };
+        panels.push(json!({
+            "id": i + 2,
+            "type": "timeseries",
+            "title": name,
+            "datasource": datasource,
+            "gridPos": { "x": (i % 2) * 12, "y": 4 + (i / 2) * 8, "w": 12, "h": 8 },
+            "targets": target(name),
+            "fieldConfig": { "defaults": { "unit": unit } },
+        }));
+    }
+    let dashboard = json!({
+        "title": "BrandMeister repeaters",
+        "uid": "check-brandmeister",
+        "tags": ["brandmeister"],
+        "schemaVersion": 39,
+        "time": { "from": "now-24h", "to": "now" },
+        "refresh": "1m",
+        "templating": {
+            "list": [
+                {
+                    "name": "datasource",
+                    "label": "Data source",
+                    "type": "datasource",
+                    "query": "prometheus",
+                },
+                {
+                    "name": "repeater",
+                    "label": "Repeater",
+                    "type": "custom",
+                    "query": repeaters.join(","),
+                    "multi": true,
+                    "includeAll": true,
+                    "current": { "text": "All", "value": "$__all" },
+                },
+            ],
+        },
+        "panels": panels,
+    });
+    serde_json::to_string_pretty(&dashboard)
+        .map(|body| body + "\n")
+        .map_err(|e| Error::parse("grafana dashboard", e))
+}
+
+/// Ids of the repeaters recorded in the status file at `path`, in ascending order.
+pub fn repeaters(path: impl AsRef<Path>) -> Result<Vec<u32>> {
+    let status = load_existing(path.as_ref())?;
+    let mut ids: Vec<u32> = status
+        .get("repeaters")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flat_map(|r| r.keys())
+        .filter_map(|id| id.parse().ok())
+        .collect();
+    ids.sort_unstable();
+    Ok(ids)
+}
+
+/// Escape `text` for inclusion in HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_html_special_characters() {
+        assert_eq!(
+            escape(r#"<b>"ON0ABC" & 'ON0XYZ'</b>"#),
+            "&lt;b&gt;&quot;ON0ABC&quot; &amp; &#39;ON0XYZ&#39;&lt;/b&gt;"
+        );
+        assert_eq!(escape("&lt;"), "&amp;lt;");
+        assert_eq!(escape("Liège"), "Liège");
+    }
+}
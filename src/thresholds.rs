@@ -0,0 +1,371 @@
+//! Nagios threshold ranges and the state they give a value, as specified by the
+//! [monitoring plugins guidelines].
+//!
+//! A range `start:end` is the zone of values that do not alert, endpoints included:
+//!
+//! | range     | alerts when                 |
+//! |-----------|-----------------------------|
+//! | `10`      | < 0 or > 10                 |
+//! | `10:`     | < 10                        |
+//! | `~:10`    | > 10                        |
+//! | `10:20`   | < 10 or > 20                |
+//! | `@10:20`  | ≥ 10 and ≤ 20               |
+//!
+//! [monitoring plugins guidelines]: https://www.monitoring-plugins.org/doc/guidelines.html#THRESHOLDFORMAT
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::check::NagiosState;
+
+/// A Nagios threshold range.
+///
+/// Example:
+/// ```
+/// use brandmeister::thresholds::Range;
+/// let range: Range = "~:600".parse().unwrap();
+/// assert!(!range.alerts(600.0));
+/// assert!(range.alerts(601.0));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Range {
+    start: f64,
+    end: f64,
+    inside: bool,
+}
+
+impl Range {
+    /// Alert when the value is outside `start..=end`. Use infinities for open ends.
+    pub fn outside(start: f64, end: f64) -> Self {
+        Range {
+            start,
+            end,
+            inside: false,
+        }
+    }
+
+    /// Alert when the value is inside `start..=end`, like `@start:end`.
+    pub fn inside(start: f64, end: f64) -> Self {
+        Range {
+            start,
+            end,
+            inside: true,
+        }
+    }
+
+    /// Alert when the value is at or above `threshold`, as the plugin's time and count
+    /// thresholds do, i.e. `@threshold:`.
+    pub fn at_or_above(threshold: f64) -> Self {
+        Range::inside(threshold, f64::INFINITY)
+    }
+
+    /// Alert when the value is at or below `threshold`, i.e. `@~:threshold`.
+    pub fn at_or_below(threshold: f64) -> Self {
+        Range::inside(f64::NEG_INFINITY, threshold)
+    }
+
+    /// Whether `value` is in the alerting zone of the range. NaN always alerts.
+    pub fn alerts(&self, value: f64) -> bool {
+        if value.is_nan() {
+            return true;
+        }
+        let within = self.start <= value && value <= self.end;
+        within == self.inside
+    }
+}
+
+/// State of `value` given optional `warning` and `critical` ranges, critical taking precedence.
+///
+/// Example:
+/// ```
+/// use brandmeister::check::NagiosState;
+/// use brandmeister::thresholds::{evaluate, Range};
+/// let warning = Range::at_or_above(600.0);
+/// let critical = Range::at_or_above(900.0);
+/// assert_eq!(evaluate(700.0, Some(&warning), Some(&critical)), NagiosState::Warning);
+/// ```
+pub fn evaluate(value: f64, warning: Option<&Range>, critical: Option<&Range>) -> NagiosState {
+    if critical.is_some_and(|r| r.alerts(value)) {
+        NagiosState::Critical
+    } else if warning.is_some_and(|r| r.alerts(value)) {
+        NagiosState::Warning
+    } else {
+        NagiosState::Ok
+    }
+}
+
+impl FromStr for Range {
+    type Err = String;
+
+    /// Parse a range in the Nagios format, e.g. `10`, `10:`, `~:10`, `10:20` or `@10:20`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid range {}", s);
+        let (inside, range) = match s.trim().strip_prefix('@') {
+            Some(range) => (true, range),
+            None => (false, s.trim()),
+        };
+        let number = |n: &str| n.parse::<f64>().ok().filter(|n| !n.is_nan());
+        let (start, end) = match range.split_once(':') {
+            Some(("", "")) => return Err(invalid()),
+            Some((start, end)) => {
+                let start = match start {
+                    "~" => f64::NEG_INFINITY,
+                    "" => 0.0,
+                    start => number(start).ok_or_else(invalid)?,
+                };
+                let end = match end {
+                    "" => f64::INFINITY,
+                    end => number(end).ok_or_else(invalid)?,
+                };
+                (start, end)
+            }
+            None => (0.0, number(range).ok_or_else(invalid)?),
+        };
+        if start > end {
+            return Err(format!("invalid range {}, start is greater than end", s));
+        }
+        Ok(Range { start, end, inside })
+    }
+}
+
+impl fmt::Display for Range {
+    /// Format in the Nagios format, omitting the default start of 0 and infinite ends.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.inside {
+            f.write_str("@")?;
+        }
+        match (self.start, self.end) {
+            (start, end) if start == 0.0 && end.is_finite() => write!(f, "{}", end),
+            (start, end) => {
+                if start == f64::NEG_INFINITY {
+                    f.write_str("~")?;
+                } else {
+                    write!(f, "{}", start)?;
+                }
+                f.write_str(":")?;
+                if end.is_finite() {
+                    write!(f, "{}", end)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(s: &str) -> Range {
+        s.parse().unwrap()
+    }
+
+    /// Values around every edge used in the tests, including infinities.
+    fn values() -> Vec<f64> {
+        let mut values = vec![f64::NEG_INFINITY, f64::INFINITY, f64::MIN, f64::MAX];
+        values.extend((-300..=300).map(|v| v as f64 / 10.0));
+        values
+    }
+
+    /// Deterministic xorshift generator for the property tests, as the crate has no
+    /// dependency to draw random cases from.
+    struct Cases(u64);
+
+    impl Cases {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        /// A finite number, among small integers, tenths and large magnitudes.
+        fn number(&mut self) -> f64 {
+            let n = (self.next() % 2001) as f64 - 1000.0;
+            match self.next() % 3 {
+                0 => n,
+                1 => n / 10.0,
+                _ => n * 1e12,
+            }
+        }
+
+        /// A range with ordered, possibly infinite, ends.
+        fn range(&mut self) -> Range {
+            let (a, b) = (self.number(), self.number());
+            let (mut start, mut end) = (a.min(b), a.max(b));
+            if self.next() & 3 == 0 {
+                start = f64::NEG_INFINITY;
+            }
+            if self.next() & 3 == 0 {
+                end = f64::INFINITY;
+            }
+            if self.next() & 1 == 0 {
+                Range::inside(start, end)
+            } else {
+                Range::outside(start, end)
+            }
+        }
+    }
+
+    const CASES: usize = 10_000;
+
+    #[test]
+    fn property_display_parse_round_trip() {
+        let mut cases = Cases(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..CASES {
+            let r = cases.range();
+            let text = r.to_string();
+            assert_eq!(text.parse::<Range>(), Ok(r), "{}", text);
+        }
+    }
+
+    #[test]
+    fn property_at_sign_inverts_alerts() {
+        let mut cases = Cases(0x2545_f491_4f6c_dd1d);
+        for _ in 0..CASES {
+            let text = cases.range().to_string();
+            let text = text.trim_start_matches('@');
+            let outside = range(text);
+            let inside = range(&format!("@{}", text));
+            for _ in 0..10 {
+                let v = cases.number();
+                assert_ne!(outside.alerts(v), inside.alerts(v), "{} at {}", text, v);
+            }
+        }
+    }
+
+    #[test]
+    fn property_start_is_at_most_end() {
+        let mut cases = Cases(0xdead_beef_cafe_f00d);
+        for _ in 0..CASES {
+            let (a, b) = (cases.number(), cases.number());
+            let parsed = format!("{}:{}", a, b).parse::<Range>();
+            assert_eq!(parsed.is_ok(), a <= b, "{}:{}", a, b);
+            if let Ok(r) = parsed {
+                assert!(r.start <= r.end, "{}:{}", a, b);
+            }
+            let r = cases.range();
+            assert!(r.start <= r.end);
+        }
+    }
+
+    #[test]
+    fn parses_guideline_examples() {
+        let cases: &[(&str, &[f64], &[f64])] = &[
+            ("10", &[-0.5, 10.5, 100.0], &[0.0, 5.0, 10.0]),
+            ("10:", &[-5.0, 9.9], &[10.0, 1e300, f64::INFINITY]),
+            (
+                "~:10",
+                &[10.1, f64::INFINITY],
+                &[f64::NEG_INFINITY, -1e300, 10.0],
+            ),
+            ("10:20", &[9.9, 20.1], &[10.0, 15.0, 20.0]),
+            ("@10:20", &[10.0, 15.0, 20.0], &[9.9, 20.1]),
+            ("-10:-5", &[-10.5, -4.5, 0.0], &[-10.0, -7.0, -5.0]),
+            ("@~:0", &[f64::NEG_INFINITY, -1.0, 0.0], &[0.1]),
+            ("0.5:1.5", &[0.4, 1.6], &[0.5, 1.0, 1.5]),
+        ];
+        for (text, alerting, quiet) in cases {
+            let r = range(text);
+            for v in *alerting {
+                assert!(r.alerts(*v), "{} should alert on {}", text, v);
+            }
+            for v in *quiet {
+                assert!(!r.alerts(*v), "{} should not alert on {}", text, v);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_ranges() {
+        for text in ["", "abc", "10:5", "@", ":", "1:2:3", "NaN", "~", "@20:10"] {
+            assert!(
+                text.parse::<Range>().is_err(),
+                "{:?} should not parse",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn nan_always_alerts() {
+        for text in ["10", "~:", "@10:20", "0:"] {
+            assert!(range(text).alerts(f64::NAN), "{} should alert on NaN", text);
+        }
+    }
+
+    #[test]
+    fn inside_is_the_complement_of_outside() {
+        for (start, end) in [
+            (0.0, 10.0),
+            (-5.0, 5.0),
+            (f64::NEG_INFINITY, 0.0),
+            (3.0, 3.0),
+        ] {
+            let outside = Range::outside(start, end);
+            let inside = Range::inside(start, end);
+            for v in values() {
+                assert_ne!(
+                    outside.alerts(v),
+                    inside.alerts(v),
+                    "{}:{} at {}",
+                    start,
+                    end,
+                    v
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for text in [
+            "10", "10:", "~:10", "10:20", "@10:20", "-10:-5", "@~:0", "~:", "0.5:1.5",
+        ] {
+            let r = range(text);
+            assert_eq!(r.to_string().parse::<Range>().unwrap(), r, "{}", text);
+        }
+        assert_eq!(range("0:10").to_string(), "10");
+        assert_eq!(range("~:10").to_string(), "~:10");
+    }
+
+    #[test]
+    fn helpers_match_comparisons() {
+        for threshold in [-10, -1, 0, 1, 600, 900] {
+            let above = Range::at_or_above(threshold as f64);
+            let below = Range::at_or_below(threshold as f64);
+            // Tenths, so that fractional values just below a threshold do not alert.
+            for v in (-10_000..=10_000).map(|v| v as f64 / 10.0) {
+                let threshold = threshold as f64;
+                assert_eq!(above.alerts(v), v >= threshold, "{} >= {}", v, threshold);
+                assert_eq!(below.alerts(v), v <= threshold, "{} <= {}", v, threshold);
+            }
+        }
+    }
+
+    #[test]
+    fn critical_takes_precedence() {
+        let warning = range("~:10");
+        let critical = range("~:20");
+        for v in values() {
+            let expected = if v > 20.0 {
+                NagiosState::Critical
+            } else if v > 10.0 {
+                NagiosState::Warning
+            } else {
+                NagiosState::Ok
+            };
+            assert_eq!(
+                evaluate(v, Some(&warning), Some(&critical)),
+                expected,
+                "{}",
+                v
+            );
+        }
+        assert_eq!(evaluate(f64::NAN, None, None), NagiosState::Ok);
+        assert_eq!(
+            evaluate(5.0, Some(&range("@0:10")), None),
+            NagiosState::Warning
+        );
+    }
+}
@@ -0,0 +1,71 @@
+//! Durations of the phases of HTTP requests, to show where a slow check spends its time.
+
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Phases of the requests made through a transport and a client that record into it.
+///
+/// [UreqTransport](crate::UreqTransport) records how long name resolution took, the time to
+/// the first byte of the response, which covers connecting, the TLS handshake and the server
+/// processing, and the time to read the body. [BmClient](crate::BmClient) records the time
+/// to parse the JSON response.
+///
+/// Example:
+/// ```no_run
+/// use std::sync::Arc;
+/// use brandmeister::{BmClient, Timings, UreqTransport};
+/// let timings = Arc::new(Timings::default());
+/// let transport = UreqTransport::with_resolve(Vec::new()).with_timings(timings.clone());
+/// let client = BmClient::with_transport(Arc::new(transport)).with_timings(timings.clone());
+/// client.last_seen_seconds(270107).unwrap();
+/// eprint!("{}", timings);
+/// ```
+#[derive(Debug, Default)]
+pub struct Timings {
+    requests: Mutex<Vec<Request>>,
+}
+
+#[derive(Debug)]
+struct Request {
+    url: String,
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl Timings {
+    /// Record that `phase` of the request of `url` took `duration`.
+    ///
+    /// The phase is added to the last request of `url` unless it already has that phase, in
+    /// which case it starts a new request, e.g. when a request is retried.
+    pub(crate) fn record(&self, url: &str, phase: &'static str, duration: Duration) {
+        let mut requests = self.requests.lock().unwrap_or_else(|e| e.into_inner());
+        match requests.iter_mut().rev().find(|r| r.url == url) {
+            Some(request) if !request.phases.iter().any(|(p, _)| *p == phase) => {
+                request.phases.push((phase, duration))
+            }
+            _ => requests.push(Request {
+                url: url.to_string(),
+                phases: vec![(phase, duration)],
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Timings {
+    /// One line per request, e.g.
+    /// `GET https://api.brandmeister.network/v2/device/270107: dns 12.4ms, first byte 183.9ms, body 0.8ms, parse 0.1ms`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let requests = self.requests.lock().unwrap_or_else(|e| e.into_inner());
+        for request in requests.iter() {
+            let phases: Vec<String> = request
+                .phases
+                .iter()
+                .map(|(phase, duration)| {
+                    format!("{} {:.1}ms", phase, duration.as_secs_f64() * 1000.0)
+                })
+                .collect();
+            writeln!(f, "GET {}: {}", request.url, phases.join(", "))?;
+        }
+        Ok(())
+    }
+}
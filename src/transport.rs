@@ -0,0 +1,565 @@
+use std::fmt::Debug;
+#[cfg(feature = "ureq")]
+use std::io::Read;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+#[cfg(feature = "ureq")]
+use std::sync::Mutex;
+#[cfg(feature = "ureq")]
+use std::time::{Duration, Instant};
+
+use serde::de::DeserializeOwned;
+use serde_json::error::Category;
+
+#[cfg(feature = "ureq")]
+use crate::Timings;
+use crate::{Error, Result};
+
+/// Response to an HTTP request.
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    /// HTTP status code
+    pub status: u16,
+    /// Response headers
+    pub headers: Vec<(String, String)>,
+    /// Response body
+    pub body: String,
+}
+
+impl HttpResponse {
+    /// Value of the first header named `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Performs the HTTP requests of the library.
+///
+/// The default [UreqTransport] requires the `ureq` feature. Implement this trait to use
+/// another HTTP stack, e.g. wasi-http when building for `wasm32-wasi`.
+pub trait Transport: Debug + Send + Sync {
+    /// GET `url` with the extra request `headers` and return the response, whatever its status.
+    fn get(&self, url: &str, headers: &[(&str, &str)]) -> Result<HttpResponse>;
+
+    /// GET `url` like [Transport::get], reading at most `limit + 1` bytes of the body so that
+    /// the caller can tell it exceeds `limit` without holding all of it in memory.
+    ///
+    /// Reads the whole body with [Transport::get] unless implemented.
+    fn get_limited(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        limit: usize,
+    ) -> Result<HttpResponse> {
+        let _ = limit;
+        self.get(url, headers)
+    }
+
+    /// PUT `body` to `url` with the extra request `headers` and return the response, whatever
+    /// its status. Only needed to push metrics, unsupported unless implemented.
+    fn put(&self, url: &str, headers: &[(&str, &str)], body: &str) -> Result<HttpResponse> {
+        let _ = (headers, body);
+        Err(Error::Transport(format!(
+            "{}: PUT requests are not supported by this transport",
+            url
+        )))
+    }
+}
+
+/// User-Agent identifying the plugin, its version and, if given, how to reach its operator,
+/// e.g. `check_brandmeister/0.3.1 (+https://github.com/sgrimee/check_brandmeister; ON4ABC@example.org)`.
+///
+/// Control characters in `contact` are dropped, as they cannot appear in a header.
+pub fn user_agent(contact: Option<&str>) -> String {
+    let mut comment = format!("+{}", env!("CARGO_PKG_REPOSITORY"));
+    if let Some(contact) = contact.map(|c| c.replace(char::is_control, "")) {
+        if !contact.trim().is_empty() {
+            comment = format!("{}; {}", comment, contact.trim());
+        }
+    }
+    format!(
+        "{}/{} ({})",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        comment
+    )
+}
+
+/// A static address for a host and port, bypassing DNS like curl's `--resolve`.
+///
+/// Example:
+/// ```
+/// use brandmeister::ResolveOverride;
+/// let entry: ResolveOverride = "api.brandmeister.network:443:192.0.2.1".parse().unwrap();
+/// assert_eq!(entry.to_string(), "api.brandmeister.network:443:192.0.2.1");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolveOverride {
+    host: String,
+    port: u16,
+    addresses: Vec<IpAddr>,
+}
+
+impl ResolveOverride {
+    /// Resolve `host` on `port` to `addresses`.
+    pub fn new(host: impl Into<String>, port: u16, addresses: Vec<IpAddr>) -> Self {
+        ResolveOverride {
+            host: host.into(),
+            port,
+            addresses,
+        }
+    }
+
+    /// Addresses to connect to for `netloc`, a `host:port` pair, if it is overridden.
+    #[cfg(feature = "ureq")]
+    fn resolve(&self, netloc: &str) -> Option<Vec<std::net::SocketAddr>> {
+        let (host, port) = netloc.rsplit_once(':')?;
+        (host.eq_ignore_ascii_case(&self.host) && port.parse() == Ok(self.port)).then(|| {
+            self.addresses
+                .iter()
+                .map(|address| std::net::SocketAddr::new(*address, self.port))
+                .collect()
+        })
+    }
+}
+
+impl FromStr for ResolveOverride {
+    type Err = String;
+
+    /// Parse `host:port:address[,address...]`, with IPv6 addresses in brackets.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid resolve entry {}, expected host:port:address", s);
+        let mut parts = s.splitn(3, ':');
+        let (Some(host), Some(port), Some(addresses)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        let port = port.parse().map_err(|_| invalid())?;
+        let addresses = addresses
+            .split(',')
+            .map(|address| {
+                let address = address.trim();
+                address
+                    .strip_prefix('[')
+                    .and_then(|a| a.strip_suffix(']'))
+                    .unwrap_or(address)
+                    .parse()
+                    .map_err(|_| format!("invalid address {} in resolve entry {}", address, s))
+            })
+            .collect::<Result<Vec<IpAddr>, String>>()?;
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        Ok(ResolveOverride::new(host, port, addresses))
+    }
+}
+
+impl std::fmt::Display for ResolveOverride {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let addresses: Vec<String> = self
+            .addresses
+            .iter()
+            .map(|address| match address {
+                IpAddr::V4(v4) => v4.to_string(),
+                IpAddr::V6(v6) => format!("[{}]", v6),
+            })
+            .collect();
+        write!(f, "{}:{}:{}", self.host, self.port, addresses.join(","))
+    }
+}
+
+/// Transport using the ureq HTTP client.
+#[cfg(feature = "ureq")]
+#[derive(Debug, Clone)]
+pub struct UreqTransport {
+    agent: ureq::Agent,
+    user_agent: String,
+    timings: Option<Arc<Timings>>,
+    /// Duration of the last name resolution, when resolving with [UreqTransport::with_resolve]
+    dns: Arc<Mutex<Option<Duration>>>,
+}
+
+#[cfg(feature = "ureq")]
+impl UreqTransport {
+    /// Use `agent` to perform the requests, identified by the default [user_agent].
+    pub fn new(agent: ureq::Agent) -> Self {
+        UreqTransport {
+            agent,
+            user_agent: user_agent(None),
+            timings: None,
+            dns: Arc::default(),
+        }
+    }
+
+    /// Send `user_agent` as the User-Agent of every request.
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Record the phases of the requests in `timings`.
+    pub fn with_timings(mut self, timings: Arc<Timings>) -> Self {
+        self.timings = Some(timings);
+        self
+    }
+
+    /// Connect to the addresses of `overrides` for their host and port, and resolve other
+    /// hosts with the system resolver.
+    pub fn with_resolve(overrides: Vec<ResolveOverride>) -> Self {
+        let dns = Arc::<Mutex<Option<Duration>>>::default();
+        let resolved = dns.clone();
+        let resolver = move |netloc: &str| -> std::io::Result<Vec<std::net::SocketAddr>> {
+            let start = Instant::now();
+            let addresses = match overrides.iter().find_map(|entry| entry.resolve(netloc)) {
+                Some(addresses) => Ok(addresses),
+                None => std::net::ToSocketAddrs::to_socket_addrs(netloc).map(Iterator::collect),
+            };
+            *resolved.lock().unwrap_or_else(|e| e.into_inner()) = Some(start.elapsed());
+            addresses
+        };
+        UreqTransport {
+            dns,
+            ..UreqTransport::new(ureq::AgentBuilder::new().resolver(resolver).build())
+        }
+    }
+}
+
+#[cfg(feature = "ureq")]
+impl Default for UreqTransport {
+    fn default() -> Self {
+        UreqTransport::new(ureq::Agent::new())
+    }
+}
+
+#[cfg(feature = "ureq")]
+impl Transport for UreqTransport {
+    fn get(&self, url: &str, headers: &[(&str, &str)]) -> Result<HttpResponse> {
+        self.get_limited(url, headers, usize::MAX)
+    }
+
+    fn get_limited(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        limit: usize,
+    ) -> Result<HttpResponse> {
+        let mut request = self.agent.get(url).set("User-Agent", &self.user_agent);
+        for (name, value) in headers {
+            request = request.set(name, value);
+        }
+        let start = Instant::now();
+        let result = request.call();
+        let headers = start.elapsed();
+        let response = ureq_response(url, result, limit);
+        if let Some(timings) = &self.timings {
+            // Resolution happens within the call before connecting, only for new connections.
+            let dns = self.dns.lock().unwrap_or_else(|e| e.into_inner()).take();
+            if let Some(dns) = dns {
+                timings.record(url, "dns", dns);
+            }
+            let first_byte = headers.saturating_sub(dns.unwrap_or_default());
+            timings.record(url, "first byte", first_byte);
+            timings.record(url, "body", start.elapsed().saturating_sub(headers));
+        }
+        response
+    }
+
+    fn put(&self, url: &str, headers: &[(&str, &str)], body: &str) -> Result<HttpResponse> {
+        let mut request = self.agent.put(url).set("User-Agent", &self.user_agent);
+        for (name, value) in headers {
+            request = request.set(name, value);
+        }
+        ureq_response(url, request.send_string(body), usize::MAX)
+    }
+}
+
+/// Convert the result of a ureq call, keeping HTTP error statuses as responses and reading at
+/// most `limit + 1` bytes of the body.
+#[cfg(feature = "ureq")]
+fn ureq_response(
+    url: &str,
+    result: std::result::Result<ureq::Response, ureq::Error>,
+    limit: usize,
+) -> Result<HttpResponse> {
+    let response = match result {
+        Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+        Err(ureq::Error::Transport(e)) => return Err(Error::Transport(e.to_string())),
+    };
+    let status = response.status();
+    let headers = response
+        .headers_names()
+        .into_iter()
+        .filter_map(|name| {
+            let value = response.header(&name)?.to_string();
+            Some((name, value))
+        })
+        .collect();
+    let mut body = Vec::new();
+    response
+        .into_reader()
+        .take(u64::try_from(limit).unwrap_or(u64::MAX).saturating_add(1))
+        .read_to_end(&mut body)
+        .map_err(|e| Error::io(format!("reading {}", url), e))?;
+    let body = if body.len() > limit {
+        // Cut anywhere, possibly within a character, and rejected by the caller anyway.
+        String::from_utf8_lossy(&body).into_owned()
+    } else {
+        String::from_utf8(body).map_err(|e| {
+            let e = std::io::Error::new(std::io::ErrorKind::InvalidData, e);
+            Error::io(format!("reading {}", url), e)
+        })?
+    };
+    Ok(HttpResponse {
+        status,
+        headers,
+        body,
+    })
+}
+
+/// The transport used when none is configured.
+pub(crate) fn default_transport() -> Result<Arc<dyn Transport>> {
+    #[cfg(feature = "ureq")]
+    return Ok(Arc::new(UreqTransport::default()));
+    #[cfg(not(feature = "ureq"))]
+    return Err(Error::InvalidOptions(
+        "no HTTP transport configured and the ureq feature is disabled".to_string(),
+    ));
+}
+
+/// GET `url` and return the body, treating HTTP error statuses as errors.
+pub(crate) fn get_body(transport: &dyn Transport, url: &str) -> Result<String> {
+    let response = transport.get(url, &[])?;
+    if response.status >= 400 {
+        return Err(Error::Http {
+            url: url.to_string(),
+            status: response.status,
+        });
+    }
+    Ok(response.body)
+}
+
+/// Longest excerpt of an unexpected response included in error messages.
+const EXCERPT_LEN: usize = 200;
+
+/// Deserialize the JSON `body` of `what`, explaining failures with the kind of mismatch, a
+/// hint at its likely cause and an excerpt of the response.
+pub(crate) fn parse_json<T: DeserializeOwned>(what: &str, body: &str) -> Result<T> {
+    serde_json::from_str(body).map_err(|e| {
+        let hint = match e.classify() {
+            Category::Syntax if body.trim_start().starts_with('<') => {
+                "the response is an HTML page, e.g. an error page or a CDN interstitial"
+            }
+            Category::Syntax => "the response is not JSON, the API may be failing",
+            Category::Eof if body.trim().is_empty() => "the response is empty",
+            Category::Eof => "the response is truncated",
+            Category::Data if matches!(body.trim(), "{}" | "[]" | "null") => {
+                "the response is empty, ensure the id is valid"
+            }
+            Category::Data if e.to_string().starts_with("missing field") => {
+                "the API schema may have changed or the id may be wrong"
+            }
+            Category::Data => "the API schema may have changed",
+            Category::Io => "the response could not be read",
+        };
+        Error::parse(
+            what,
+            format!("{}, {}; response: {}", e, hint, excerpt(body)),
+        )
+    })
+}
+
+/// Start of `body` on a single line, for error messages.
+pub(crate) fn excerpt(body: &str) -> String {
+    let excerpt: String = body.split_whitespace().collect::<Vec<_>>().join(" ");
+    if excerpt.chars().count() > EXCERPT_LEN {
+        return excerpt.chars().take(EXCERPT_LEN).collect::<String>() + "...";
+    }
+    excerpt
+}
+
+/// Percent-encode a query string value.
+pub(crate) fn encode_query(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for b in value.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(b as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+/// Canned API responses for the unit tests.
+#[cfg(test)]
+pub(crate) mod stub {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// URL and headers of a request.
+    type Request = (String, Vec<(String, String)>);
+
+    /// Transport answering each request with the responses registered for the end of its URL,
+    /// in order and repeating the last one, or 404 when none matches.
+    #[derive(Debug, Default)]
+    pub(crate) struct StubTransport {
+        responses: Mutex<Vec<(String, HttpResponse)>>,
+        requests: Mutex<Vec<Request>>,
+        delay: std::time::Duration,
+    }
+
+    impl StubTransport {
+        pub(crate) fn new() -> Self {
+            Self::default()
+        }
+
+        /// Answer the requests to URLs ending with `suffix` with `status` and `body`.
+        pub(crate) fn with(self, suffix: &str, status: u16, body: &str) -> Self {
+            self.with_headers(suffix, status, &[], body)
+        }
+
+        /// Answer the requests to URLs ending with `suffix` with `status`, `headers` and `body`.
+        pub(crate) fn with_headers(
+            self,
+            suffix: &str,
+            status: u16,
+            headers: &[(&str, &str)],
+            body: &str,
+        ) -> Self {
+            let response = HttpResponse {
+                status,
+                headers: owned(headers),
+                body: body.to_string(),
+            };
+            self.responses
+                .lock()
+                .unwrap()
+                .push((suffix.to_string(), response));
+            self
+        }
+
+        /// Take `delay` to answer each request.
+        pub(crate) fn with_delay(mut self, delay: std::time::Duration) -> Self {
+            self.delay = delay;
+            self
+        }
+
+        /// URLs requested so far, in order.
+        pub(crate) fn urls(&self) -> Vec<String> {
+            let requests = self.requests.lock().unwrap();
+            requests.iter().map(|(url, _)| url.clone()).collect()
+        }
+
+        /// Headers sent with each request so far, in order.
+        pub(crate) fn headers(&self) -> Vec<Vec<(String, String)>> {
+            let requests = self.requests.lock().unwrap();
+            requests
+                .iter()
+                .map(|(_, headers)| headers.clone())
+                .collect()
+        }
+    }
+
+    fn owned(headers: &[(&str, &str)]) -> Vec<(String, String)> {
+        headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    impl Transport for StubTransport {
+        fn get(&self, url: &str, headers: &[(&str, &str)]) -> Result<HttpResponse> {
+            let request = (url.to_string(), owned(headers));
+            self.requests.lock().unwrap().push(request);
+            std::thread::sleep(self.delay);
+            let mut responses = self.responses.lock().unwrap();
+            let matching: Vec<usize> = (0..responses.len())
+                .filter(|&i| url.ends_with(&responses[i].0))
+                .collect();
+            Ok(match matching[..] {
+                [] => HttpResponse {
+                    status: 404,
+                    headers: Vec::new(),
+                    body: String::new(),
+                },
+                [last] => responses[last].1.clone(),
+                [first, ..] => responses.remove(first).1,
+            })
+        }
+    }
+}
+
+#[cfg(all(test, feature = "ureq"))]
+mod tests {
+    use std::io::Write;
+    use std::net::TcpListener;
+
+    use super::*;
+
+    /// Answer one HTTP request on a local port with a body of `size` bytes, returning its URL.
+    fn serve_body(size: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/v2/device/270107", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 4096];
+            while !String::from_utf8_lossy(&request).contains("\r\n\r\n") {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                size
+            );
+            // The client may stop reading and close the connection before the end.
+            let _ = stream
+                .write_all(head.as_bytes())
+                .and_then(|_| stream.write_all(&vec![b'a'; size]));
+        });
+        url
+    }
+
+    #[test]
+    fn body_read_stops_past_the_limit() {
+        let url = serve_body(1 << 20);
+        let response = UreqTransport::default()
+            .get_limited(&url, &[], 100)
+            .unwrap();
+        assert_eq!(response.body.len(), 101);
+    }
+
+    #[test]
+    fn body_within_the_limit_is_read_whole() {
+        let url = serve_body(100);
+        let response = UreqTransport::default()
+            .get_limited(&url, &[], 100)
+            .unwrap();
+        assert_eq!(response.body, "a".repeat(100));
+    }
+
+    #[test]
+    fn client_rejects_bodies_over_the_limit() {
+        let url = serve_body(1 << 20);
+        let api_url = url.trim_end_matches("/device/270107");
+        let e = crate::BmClient::new()
+            .with_api_url(api_url)
+            .with_max_response_size(100)
+            .device_record(270107)
+            .unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            format!(
+                "error parsing brandmeister API result for {}: the response exceeds the limit \
+                 of 100 bytes",
+                url
+            )
+        );
+    }
+}
@@ -0,0 +1,375 @@
+//! HTTP transport over a Unix domain socket, to reach a local caching proxy without TCP or TLS.
+//!
+//! Requests are plain HTTP/1.1 with `Connection: close`, one connection per request, which is
+//! all a local sidecar needs.
+
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::transport::{user_agent, HttpResponse, Transport};
+use crate::{Error, Result};
+
+/// How long to wait for the proxy when connecting, writing or reading.
+const TIMEOUT: Duration = Duration::from_secs(30);
+/// Most redirects followed for a request, like ureq.
+const MAX_REDIRECTS: usize = 5;
+/// Longest response head and chunk size line accepted.
+const MAX_HEAD_SIZE: u64 = 64 * 1024;
+
+/// Transport sending the requests to the HTTP server listening on a Unix socket.
+///
+/// The path and host of the requested URLs are sent as is, whatever the socket.
+///
+/// Example:
+/// ```no_run
+/// use std::sync::Arc;
+/// use brandmeister::{BmClient, UnixSocketTransport};
+/// let client = BmClient::with_transport(Arc::new(UnixSocketTransport::new("/run/bm.sock")))
+///     .with_api_url("http://localhost/v2");
+/// ```
+#[derive(Debug, Clone)]
+pub struct UnixSocketTransport {
+    path: PathBuf,
+    user_agent: String,
+}
+
+impl UnixSocketTransport {
+    /// Connect to the socket at `path` for each request.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        UnixSocketTransport {
+            path: path.into(),
+            user_agent: user_agent(None),
+        }
+    }
+
+    /// Send `user_agent` as the User-Agent of every request.
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Send a `method` request and read the response, with at most `limit + 1` bytes of body.
+    fn request(
+        &self,
+        method: &str,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: &str,
+        limit: usize,
+    ) -> Result<HttpResponse> {
+        let transport_error = |e: std::io::Error| {
+            Error::Transport(format!("{} via {}: {}", url, self.path.display(), e))
+        };
+        let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+        let (host, target) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        let mut request = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: {}\r\nConnection: close\r\n\
+             Content-Length: {}\r\n",
+            method,
+            target,
+            host,
+            self.user_agent,
+            body.len()
+        );
+        for (name, value) in headers {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        request.push_str("\r\n");
+        request.push_str(body);
+
+        let mut stream = UnixStream::connect(&self.path).map_err(transport_error)?;
+        stream
+            .set_read_timeout(Some(TIMEOUT))
+            .and_then(|_| stream.set_write_timeout(Some(TIMEOUT)))
+            .map_err(transport_error)?;
+        stream
+            .write_all(request.as_bytes())
+            .map_err(transport_error)?;
+        parse_response(BufReader::new(stream), limit).map_err(|e| match e.kind() {
+            ErrorKind::InvalidData | ErrorKind::UnexpectedEof => Error::Transport(format!(
+                "{} via {}: invalid HTTP response",
+                url,
+                self.path.display()
+            )),
+            _ => transport_error(e),
+        })
+    }
+}
+
+impl Transport for UnixSocketTransport {
+    fn get(&self, url: &str, headers: &[(&str, &str)]) -> Result<HttpResponse> {
+        self.get_limited(url, headers, usize::MAX)
+    }
+
+    fn get_limited(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        limit: usize,
+    ) -> Result<HttpResponse> {
+        let mut url = url.to_string();
+        let mut response = self.request("GET", &url, headers, "", limit)?;
+        for _ in 0..MAX_REDIRECTS {
+            let location = match (response.status, response.header("Location")) {
+                (301 | 302 | 303 | 307 | 308, Some(location)) => location.to_string(),
+                _ => break,
+            };
+            url = redirect_url(&url, &location);
+            response = self.request("GET", &url, headers, "", limit)?;
+        }
+        Ok(response)
+    }
+
+    fn put(&self, url: &str, headers: &[(&str, &str)], body: &str) -> Result<HttpResponse> {
+        self.request("PUT", url, headers, body, usize::MAX)
+    }
+}
+
+/// Resolve a redirect `location`, either absolute, relative to the scheme, the server or the
+/// path of `url`.
+fn redirect_url(url: &str, location: &str) -> String {
+    if location.contains("://") {
+        return location.to_string();
+    }
+    let scheme_end = url.find("://").map_or(0, |i| i + 3);
+    if let Some(rest) = location.strip_prefix("//") {
+        return format!("{}{}", &url[..scheme_end], rest);
+    }
+    let origin_end = url[scheme_end..]
+        .find('/')
+        .map_or(url.len(), |i| scheme_end + i);
+    let (origin, path) = url.split_at(origin_end);
+    if location.starts_with('/') {
+        return format!("{}{}", origin, location);
+    }
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let directory = path.rfind('/').map_or("/", |i| &path[..=i]);
+    format!("{}{}{}", origin, directory, location)
+}
+
+/// Read an HTTP/1.1 response, decoding a chunked body and reading at most `limit + 1` bytes
+/// of it.
+///
+/// Malformed responses are [ErrorKind::InvalidData] errors, and responses cut short
+/// [ErrorKind::UnexpectedEof] ones.
+fn parse_response(mut response: impl BufRead, limit: usize) -> std::io::Result<HttpResponse> {
+    let invalid = || std::io::Error::from(ErrorKind::InvalidData);
+    let head = read_line(&mut response, b"\r\n\r\n")?;
+    let head = std::str::from_utf8(&head).map_err(|_| invalid())?;
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1)?.parse().ok())
+        .ok_or_else(invalid)?;
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect();
+    let mut response_head = HttpResponse {
+        status,
+        headers,
+        body: String::new(),
+    };
+    let chunked = response_head
+        .header("Transfer-Encoding")
+        .is_some_and(|e| e.eq_ignore_ascii_case("chunked"));
+    let max = limit.saturating_add(1);
+    let mut body = Vec::new();
+    if chunked {
+        while body.len() < max {
+            let line = read_line(&mut response, b"\r\n")?;
+            let size = std::str::from_utf8(&line).map_err(|_| invalid())?;
+            let size = size.split(';').next().unwrap_or_default().trim();
+            let size = usize::from_str_radix(size, 16).map_err(|_| invalid())?;
+            if size == 0 {
+                break;
+            }
+            // Stop within the chunk that exceeds the limit, the rest is not needed.
+            let wanted = size.min(max - body.len());
+            read_exactly(&mut response, wanted, &mut body)?;
+            if wanted == size && read_line(&mut response, b"\r\n")? != b"" {
+                return Err(invalid());
+            }
+        }
+    } else if let Some(length) = response_head.header("Content-Length") {
+        let length: usize = length.parse().map_err(|_| invalid())?;
+        // A shorter body means the connection was closed before the end of the response.
+        read_exactly(&mut response, length.min(max), &mut body)?;
+    } else {
+        (&mut response).take(max as u64).read_to_end(&mut body)?;
+    }
+    response_head.body = if body.len() > limit {
+        // Cut anywhere, possibly within a character, and rejected by the caller anyway.
+        String::from_utf8_lossy(&body).into_owned()
+    } else {
+        String::from_utf8(body).map_err(|_| invalid())?
+    };
+    Ok(response_head)
+}
+
+/// Read up to the first `terminator`, returning what precedes it.
+fn read_line(reader: &mut impl BufRead, terminator: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut line = Vec::new();
+    let mut limited = reader.take(MAX_HEAD_SIZE);
+    while !line.ends_with(terminator) {
+        if limited.read_until(b'\n', &mut line)? == 0 {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+    }
+    line.truncate(line.len() - terminator.len());
+    Ok(line)
+}
+
+/// Append exactly `length` bytes from `reader` to `buffer`, without allocating them upfront.
+fn read_exactly(
+    reader: &mut impl Read,
+    length: usize,
+    buffer: &mut Vec<u8>,
+) -> std::io::Result<()> {
+    let start = buffer.len();
+    reader.take(length as u64).read_to_end(buffer)?;
+    if buffer.len() - start < length {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(response: &[u8]) -> Option<HttpResponse> {
+        parse_response(response, usize::MAX).ok()
+    }
+
+    #[test]
+    fn content_length_body() {
+        let response =
+            parse(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nX-A: b: c\r\n\r\n{}").unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("content-length"), Some("2"));
+        assert_eq!(response.header("X-A"), Some("b: c"));
+        assert_eq!(response.body, "{}");
+
+        let extra = parse(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}\r\n").unwrap();
+        assert_eq!(extra.body, "{}");
+        let empty = parse(b"HTTP/1.1 304 Not Modified\r\n\r\n").unwrap();
+        assert_eq!((empty.status, empty.body.as_str()), (304, ""));
+    }
+
+    #[test]
+    fn truncated_content_length_body() {
+        assert!(parse(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n{}").is_none());
+        assert!(parse(b"HTTP/1.1 200 OK\r\nContent-Length: ten\r\n\r\n{}").is_none());
+    }
+
+    #[test]
+    fn chunked_body() {
+        let response = parse(
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: Chunked\r\n\r\n\
+              5\r\n{\"id\"\r\na;ext=1\r\n: 270107}\n\r\n0\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(response.body, "{\"id\": 270107}\n");
+    }
+
+    #[test]
+    fn truncated_chunk() {
+        let head = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+        let bodies: [&[u8]; 6] = [
+            b"",
+            b"4\r\n{\"i",
+            b"4\r\n{\"id\"",
+            b"4\r\n{\"id\"xx2\r\n{}\r\n0\r\n\r\n",
+            b"zz\r\n{}\r\n0\r\n\r\n",
+            b"ffffffffffffffff\r\n{}\r\n0\r\n\r\n",
+        ];
+        for body in bodies {
+            let response = [head.as_slice(), body].concat();
+            assert!(
+                parse(&response).is_none(),
+                "{}",
+                String::from_utf8_lossy(body)
+            );
+        }
+    }
+
+    #[test]
+    fn body_read_stops_past_the_limit() {
+        let body = "a".repeat(1000);
+        let length = format!("HTTP/1.1 200 OK\r\nContent-Length: 1000\r\n\r\n{}", body);
+        let chunked = format!(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3e8\r\n{}\r\n0\r\n\r\n",
+            body
+        );
+        let until_close = format!("HTTP/1.1 200 OK\r\n\r\n{}", body);
+        for response in [length, chunked, until_close] {
+            let limited = parse_response(response.as_bytes(), 100).unwrap();
+            assert_eq!(limited.body.len(), 101, "{}", response);
+            let whole = parse_response(response.as_bytes(), 1000).unwrap();
+            assert_eq!(whole.body, body);
+        }
+    }
+
+    #[test]
+    fn socket_body_read_stops_past_the_limit() {
+        let path = std::env::temp_dir().join(format!("check_bm_limit_{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = BufReader::new(stream.try_clone().unwrap());
+            read_line(&mut request, b"\r\n\r\n").unwrap();
+            let size = 1 << 20;
+            let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", size);
+            // The client stops reading and closes the connection before the end.
+            let _ = stream
+                .write_all(head.as_bytes())
+                .and_then(|_| stream.write_all(&vec![b'a'; size]));
+        });
+        let response = UnixSocketTransport::new(&path)
+            .get_limited("http://localhost/v2/device/270107", &[], 100)
+            .unwrap();
+        assert_eq!(response.body.len(), 101);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn missing_status_line() {
+        assert!(parse(b"").is_none());
+        assert!(parse(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n").is_none());
+        assert!(parse(b"\r\n\r\n{}").is_none());
+        assert!(parse(b"HTTP/1.1\r\n\r\n{}").is_none());
+        assert!(parse(b"HTTP/1.1 OK\r\n\r\n{}").is_none());
+    }
+
+    #[test]
+    fn redirect_locations() {
+        let url = "http://localhost/v2/device/270107?x=1";
+        let resolve = |location| redirect_url(url, location);
+        assert_eq!(resolve("https://api.example/v2"), "https://api.example/v2");
+        assert_eq!(resolve("//proxy/v2/device"), "http://proxy/v2/device");
+        assert_eq!(
+            resolve("/v3/device/270107"),
+            "http://localhost/v3/device/270107"
+        );
+        assert_eq!(resolve("270108"), "http://localhost/v2/device/270108");
+        assert_eq!(
+            resolve("../profile"),
+            "http://localhost/v2/device/../profile"
+        );
+        assert_eq!(
+            redirect_url("http://localhost", "v2"),
+            "http://localhost/v2"
+        );
+        assert_eq!(
+            redirect_url("http://localhost/", "v2"),
+            "http://localhost/v2"
+        );
+    }
+}